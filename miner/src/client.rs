@@ -0,0 +1,475 @@
+//! Talks to a Crain node over the `crain_mining` JSON-RPC namespace.
+
+use crain_pow::{
+	miner::{MiningJob, WorkSource},
+	HashAlgorithm, Solution,
+};
+use log::*;
+use parity_scale_codec::Encode;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use sp_core::{H256, U256};
+use std::{
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, RwLock,
+	},
+	thread,
+	time::Duration,
+};
+
+// Longest pause between two attempts to reach a node that is down
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+// Work as returned by `crain_mining_getWork`
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Work {
+	job_id: u64,
+	parent_hash: H256,
+	pre_hash: H256,
+	difficulty: U256,
+	algorithm: String,
+}
+
+// The `crain_mining` calls the miner makes
+pub trait NodeRpc: Clone + Send + Sync + 'static {
+	/// Address of the node, for the logs
+	fn url(&self) -> &str;
+
+	/// Current work of the node, if it has any
+	fn get_work(&self) -> Result<Option<Work>, String>;
+
+	/// Send a solution, `true` if the node sealed a block with it
+	fn submit_work(&self, solution: &Solution) -> Result<bool, String>;
+}
+
+// Blocking JSON-RPC client of a single node
+#[derive(Clone)]
+pub struct NodeClient {
+	url: String,
+	agent: ureq::Agent,
+}
+
+impl NodeClient {
+	pub fn new(url: String) -> Self {
+		let agent = ureq::AgentBuilder::new().timeout(Duration::from_secs(10)).build();
+		Self { url, agent }
+	}
+
+	fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+		let request = json!({
+			"jsonrpc": "2.0",
+			"id": 1,
+			"method": method,
+			"params": params,
+		});
+
+		let mut response: Value = self
+			.agent
+			.post(&self.url)
+			.send_json(request)
+			.map_err(|e| format!("Request to {} failed: {}", self.url, e))?
+			.into_json()
+			.map_err(|e| format!("Invalid response from {}: {}", self.url, e))?;
+
+		if let Some(error) = response.get("error") {
+			return Err(format!("{} returned an error: {}", method, error))
+		}
+
+		serde_json::from_value(response["result"].take())
+			.map_err(|e| format!("Invalid result of {}: {}", method, e))
+	}
+}
+
+impl NodeRpc for NodeClient {
+	fn url(&self) -> &str {
+		&self.url
+	}
+
+	fn get_work(&self) -> Result<Option<Work>, String> {
+		self.call("crain_mining_getWork", json!([]))
+	}
+
+	fn submit_work(&self, solution: &Solution) -> Result<bool, String> {
+		self.call(
+			"crain_mining_submitWork",
+			json!([format!("0x{}", hex::encode(solution.encode()))]),
+		)
+	}
+}
+
+// Work source backed by a node. A background thread polls the node for work and the mining
+// threads only read the last known job, so they never wait for the network.
+#[derive(Clone)]
+pub struct RpcWorkSource<R = NodeClient> {
+	client: R,
+	job: Arc<RwLock<Option<MiningJob>>>,
+	// Hash algorithm of the chain, known after the first work arrives
+	hasher: Arc<RwLock<Option<HashAlgorithm>>>,
+	// Local job counter. The node's own job ids start over when the node restarts,
+	// so they are not used to tell jobs apart.
+	job_id: Arc<AtomicUsize>,
+}
+
+impl<R: NodeRpc> RpcWorkSource<R> {
+	pub fn new(client: R) -> Self {
+		Self {
+			client,
+			job: Arc::new(RwLock::new(None)),
+			hasher: Arc::new(RwLock::new(None)),
+			job_id: Arc::new(AtomicUsize::new(0)),
+		}
+	}
+
+	// Block until the node has told which hash algorithm its chain uses
+	pub fn wait_for_hasher(&self) -> HashAlgorithm {
+		loop {
+			if let Some(hasher) = *self.hasher.read().expect("Hasher lock is never poisoned; qed") {
+				return hasher
+			}
+			thread::sleep(Duration::from_millis(100));
+		}
+	}
+
+	// Start polling the node for work in the background
+	pub fn start_polling(&self, interval: Duration) {
+		let source = self.clone();
+		thread::Builder::new()
+			.name("crain-miner-poll".into())
+			.spawn(move || source.poll(interval))
+			.expect("Spawning the polling thread must not fail; qed");
+	}
+
+	fn poll(&self, interval: Duration) {
+		let mut last_work: Option<Work> = None;
+		let mut reconnect_delay = interval;
+		let mut connected = false;
+
+		loop {
+			match self.refresh(&mut last_work) {
+				Ok(()) => {
+					if !connected {
+						info!("Connected to {}", self.client.url());
+						connected = true;
+					}
+					reconnect_delay = interval;
+					thread::sleep(interval);
+				},
+				Err(e) => {
+					if connected {
+						warn!("Lost connection to the node: {}", e);
+						connected = false;
+					} else {
+						debug!("{}", e);
+					}
+					// Stop mining on work that is probably stale by now
+					if last_work.take().is_some() {
+						self.set_job(None);
+					}
+
+					info!("Reconnecting in {} s", reconnect_delay.as_secs_f32());
+					thread::sleep(reconnect_delay);
+					reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+				},
+			}
+		}
+	}
+
+	// Ask the node for work and switch to it if it is not the work seen last time. A job that
+	// was dropped after its solution got accepted stays dropped until the node has new work.
+	fn refresh(&self, last_work: &mut Option<Work>) -> Result<(), String> {
+		let work = self.client.get_work()?;
+		if work != *last_work {
+			if work.is_none() {
+				info!("The node has no work, waiting");
+			}
+			self.set_job(work.as_ref());
+			*last_work = work;
+		}
+		Ok(())
+	}
+
+	fn set_job(&self, work: Option<&Work>) {
+		let work = work.filter(|work| self.check_hasher(&work.algorithm));
+		let id = self.job_id.fetch_add(1, Ordering::SeqCst) + 1;
+		let job = work.map(|work| {
+			debug!("New job {} from the node: {:?}", work.job_id, work.pre_hash);
+			MiningJob {
+				id,
+				parent: work.parent_hash,
+				pre_hash: work.pre_hash,
+				difficulty: work.difficulty,
+			}
+		});
+		*self.job.write().expect("Job lock is never poisoned; qed") = job;
+	}
+
+	// Stop mining on the job with the given id, unless the poller already replaced it
+	fn drop_job(&self, id: usize) {
+		let mut job = self.job.write().expect("Job lock is never poisoned; qed");
+		if job.as_ref().map(|job| job.id) == Some(id) {
+			*job = None;
+			self.job_id.fetch_add(1, Ordering::SeqCst);
+		}
+	}
+
+	// The mining threads keep the hasher they were started with,
+	// so work for any other hash algorithm is ignored
+	fn check_hasher(&self, algorithm: &str) -> bool {
+		let algorithm = match algorithm.parse::<HashAlgorithm>() {
+			Ok(algorithm) => algorithm,
+			Err(e) => {
+				error!("{}", e);
+				return false
+			},
+		};
+		// The datasets are built from the chain, which only the node has
+		if algorithm.plain_hasher().is_none() {
+			error!("The chain uses the {} algorithm, mine with the node instead", algorithm);
+			return false
+		}
+
+		let mut hasher = self.hasher.write().expect("Hasher lock is never poisoned; qed");
+		match *hasher {
+			Some(hasher) if hasher != algorithm => {
+				error!("The node switched from {} to {}, restart the miner", hasher, algorithm);
+				false
+			},
+			Some(_) => true,
+			None => {
+				info!("The chain uses the {} hash algorithm", algorithm);
+				*hasher = Some(algorithm);
+				true
+			},
+		}
+	}
+}
+
+impl<R: NodeRpc> WorkSource for RpcWorkSource<R> {
+	fn job_id(&self) -> usize {
+		self.job_id.load(Ordering::SeqCst)
+	}
+
+	fn work(&self) -> Option<MiningJob> {
+		self.job.read().expect("Job lock is never poisoned; qed").clone()
+	}
+
+	fn submit(&self, solution: Solution) -> bool {
+		let id = self.job_id();
+		match self.client.submit_work(&solution) {
+			Ok(true) => {
+				info!("Solution with nonce {} accepted by the node", solution.nonce);
+				// The block is sealed, its job would only produce orphans until the next poll
+				self.drop_job(id);
+				true
+			},
+			Ok(false) => {
+				warn!("Solution with nonce {} rejected by the node", solution.nonce);
+				false
+			},
+			Err(e) => {
+				warn!("Unable to submit a solution: {}", e);
+				false
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{
+		sync::{atomic::AtomicBool, Mutex},
+		time::Instant,
+	};
+
+	// Node that hands out the work it was given and records the solutions it gets
+	#[derive(Clone, Default)]
+	struct MockNode {
+		work: Arc<Mutex<Option<Work>>>,
+		reject: Arc<AtomicBool>,
+		submitted: Arc<Mutex<Vec<Solution>>>,
+	}
+
+	impl MockNode {
+		fn set_work(&self, work: Option<Work>) {
+			*self.work.lock().unwrap() = work;
+		}
+
+		fn submitted(&self) -> usize {
+			self.submitted.lock().unwrap().len()
+		}
+	}
+
+	impl NodeRpc for MockNode {
+		fn url(&self) -> &str {
+			"mock"
+		}
+
+		fn get_work(&self) -> Result<Option<Work>, String> {
+			Ok(self.work.lock().unwrap().clone())
+		}
+
+		fn submit_work(&self, solution: &Solution) -> Result<bool, String> {
+			self.submitted.lock().unwrap().push(solution.clone());
+			Ok(!self.reject.load(Ordering::SeqCst))
+		}
+	}
+
+	fn work(job_id: u64, difficulty: u64, algorithm: &str) -> Work {
+		Work {
+			job_id,
+			parent_hash: H256::repeat_byte(job_id as u8 + 1),
+			pre_hash: H256::repeat_byte(job_id as u8),
+			difficulty: U256::from(difficulty),
+			algorithm: algorithm.into(),
+		}
+	}
+
+	fn solution() -> Solution {
+		Solution { difficulty: U256::one(), work: H256::zero(), nonce: U256::zero() }
+	}
+
+	fn source() -> (MockNode, RpcWorkSource<MockNode>) {
+		let node = MockNode::default();
+		(node.clone(), RpcWorkSource::new(node))
+	}
+
+	// Wait for the condition for a few seconds, the mining threads look for work every second
+	fn wait_until(condition: impl Fn() -> bool) -> bool {
+		let deadline = Instant::now() + Duration::from_secs(10);
+		while Instant::now() < deadline {
+			if condition() {
+				return true
+			}
+			thread::sleep(Duration::from_millis(10));
+		}
+		false
+	}
+
+	#[test]
+	fn new_work_switches_the_job() {
+		let (node, source) = source();
+		let mut last_work = None;
+
+		node.set_work(Some(work(1, 100, "sha3")));
+		source.refresh(&mut last_work).unwrap();
+		let first = source.work().unwrap();
+		assert_eq!(first.pre_hash, H256::repeat_byte(1));
+		assert_eq!(first.difficulty, U256::from(100));
+		assert_eq!(source.job_id(), first.id);
+
+		// The same work keeps the job
+		source.refresh(&mut last_work).unwrap();
+		assert_eq!(source.work(), Some(first.clone()));
+
+		node.set_work(Some(work(2, 200, "sha3")));
+		source.refresh(&mut last_work).unwrap();
+		let second = source.work().unwrap();
+		assert_eq!(second.pre_hash, H256::repeat_byte(2));
+		assert!(second.id > first.id);
+
+		node.set_work(None);
+		source.refresh(&mut last_work).unwrap();
+		assert_eq!(source.work(), None);
+		assert!(source.job_id() > second.id);
+	}
+
+	#[test]
+	fn work_for_another_algorithm_is_ignored() {
+		let (node, source) = source();
+		let mut last_work = None;
+
+		node.set_work(Some(work(1, 100, "sha3")));
+		source.refresh(&mut last_work).unwrap();
+		assert_eq!(source.wait_for_hasher(), HashAlgorithm::Sha3);
+
+		node.set_work(Some(work(2, 100, "keccak")));
+		source.refresh(&mut last_work).unwrap();
+		assert_eq!(source.work(), None);
+	}
+
+	#[test]
+	fn memory_hard_work_is_ignored() {
+		let (node, source) = source();
+		let mut last_work = None;
+
+		node.set_work(Some(work(1, 100, "memory-hard")));
+		source.refresh(&mut last_work).unwrap();
+		assert_eq!(source.work(), None);
+	}
+
+	#[test]
+	fn accepted_solution_drops_the_job() {
+		let (node, source) = source();
+		let mut last_work = None;
+
+		node.set_work(Some(work(1, 100, "sha3")));
+		source.refresh(&mut last_work).unwrap();
+		let id = source.job_id();
+
+		assert!(source.submit(solution()));
+		assert_eq!(source.work(), None);
+		assert!(source.job_id() > id);
+
+		// Until the node built on the new block it still hands out the solved work
+		source.refresh(&mut last_work).unwrap();
+		assert_eq!(source.work(), None);
+
+		node.set_work(Some(work(2, 100, "sha3")));
+		source.refresh(&mut last_work).unwrap();
+		assert_eq!(source.work().unwrap().pre_hash, H256::repeat_byte(2));
+	}
+
+	#[test]
+	fn rejected_solution_keeps_the_job() {
+		let (node, source) = source();
+		let mut last_work = None;
+
+		node.set_work(Some(work(1, 100, "sha3")));
+		node.reject.store(true, Ordering::SeqCst);
+		source.refresh(&mut last_work).unwrap();
+		let job = source.work();
+
+		assert!(!source.submit(solution()));
+		assert_eq!(source.work(), job);
+	}
+
+	#[test]
+	fn accepted_solution_does_not_drop_newer_work() {
+		let (node, source) = source();
+		let mut last_work = None;
+
+		node.set_work(Some(work(1, 100, "sha3")));
+		source.refresh(&mut last_work).unwrap();
+		let id = source.job_id();
+
+		node.set_work(Some(work(2, 100, "sha3")));
+		source.refresh(&mut last_work).unwrap();
+		source.drop_job(id);
+		assert_eq!(source.work().unwrap().pre_hash, H256::repeat_byte(2));
+	}
+
+	#[test]
+	fn mining_threads_submit_once_per_job() {
+		let (node, source) = source();
+		let mut last_work = None;
+
+		// Every hash meets difficulty 1
+		node.set_work(Some(work(1, 1, "sha3")));
+		source.refresh(&mut last_work).unwrap();
+		crain_pow::miner::start_mining_threads(source.clone(), HashAlgorithm::Sha3, 1);
+
+		assert!(wait_until(|| node.submitted() >= 1));
+		// The threads stop as soon as the first solution is accepted
+		thread::sleep(Duration::from_millis(200));
+		assert_eq!(node.submitted(), 1);
+
+		node.set_work(Some(work(2, 1, "sha3")));
+		source.refresh(&mut last_work).unwrap();
+		assert!(wait_until(|| node.submitted() >= 2));
+		thread::sleep(Duration::from_millis(200));
+		assert_eq!(node.submitted(), 2);
+	}
+}
@@ -0,0 +1,52 @@
+//! Standalone CPU miner. Takes work from a Crain node and sends found solutions back to it,
+//! the node seals the block with its author key.
+
+mod client;
+
+use clap::Parser;
+use client::{NodeClient, RpcWorkSource};
+use log::*;
+use std::{sync::atomic::Ordering, thread, time::Duration};
+
+#[derive(Debug, clap::Parser)]
+#[clap(about = "Standalone CPU miner for CRAIN Network")]
+struct Cli {
+	/// HTTP RPC endpoint of the node to mine for.
+	#[clap(long, default_value = "http://127.0.0.1:9933")]
+	url: String,
+
+	/// Number of threads used for mining.
+	#[clap(long, default_value = "1")]
+	threads: usize,
+
+	/// How often to ask the node for new work, in milliseconds.
+	#[clap(long, default_value = "500")]
+	poll_interval: u64,
+
+	/// How often to print the hashrate, in seconds.
+	#[clap(long, default_value = "10")]
+	report_interval: u64,
+}
+
+fn main() {
+	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+	let cli = Cli::parse();
+
+	let source = RpcWorkSource::new(NodeClient::new(cli.url));
+	source.start_polling(Duration::from_millis(cli.poll_interval));
+
+	let hasher = source.wait_for_hasher();
+	let hashes = crain_pow::miner::start_mining_threads(source, hasher, cli.threads);
+
+	// Hashrate readout
+	let report_interval = Duration::from_secs(cli.report_interval.max(1));
+	let mut last = 0;
+	loop {
+		thread::sleep(report_interval);
+		let total = hashes.load(Ordering::Relaxed);
+		let rate = (total - last) as f64 / report_interval.as_secs_f64();
+		last = total;
+		info!("Hashrate: {:.2} H/s ({} hashes in total)", rate, total);
+	}
+}
@@ -2,6 +2,7 @@ use crain_runtime::{
 	AccountId, BalancesConfig, DifficultyConfig, GenesisConfig, Signature,
 	SystemConfig, WASM_BINARY,
 };
+use crain_pow::HashAlgorithm;
 use sc_service::ChainType;
 use sp_core::{sr25519, Pair, Public, U256};
 use sp_runtime::traits::{IdentifyAccount, Verify};
@@ -35,6 +36,8 @@ fn testnet_genesis(
 	wasm_binary: &[u8],
 	// Initial difficulty for `difficulty` pallet
 	initial_difficulty: U256,
+	// PoW algorithm, the node reads it from the runtime
+	pow_algorithm: HashAlgorithm,
 	// Prefunded accounts
 	endowed_accounts: Vec<AccountId>,
 	_enable_println: bool,
@@ -51,7 +54,7 @@ fn testnet_genesis(
 			.map(|k| (k, 1 << 60))
 			.collect(),
 		},
-		difficulty: DifficultyConfig { initial_difficulty },
+		difficulty: DifficultyConfig { initial_difficulty, pow_algorithm },
 		..Default::default()
 	}
 }
@@ -70,6 +73,7 @@ pub fn development_config() -> Result<ChainSpec, String> {
 				wasm_binary,
 				// NOTE The higher the longer mines
 				U256::from(1_000_000),
+				HashAlgorithm::Sha3,
 				vec![
 					get_account_id_from_seed::<sr25519::Public>("Alice"),
 					get_account_id_from_seed::<sr25519::Public>("Bob"),
@@ -115,6 +119,7 @@ pub fn local_testnet_config() -> Result<ChainSpec, String> {
 			testnet_genesis(
 				wasm_binary,
 				U256::from(1000),
+				HashAlgorithm::Sha3,
 				vec![
 					get_account_id_from_seed::<sr25519::Public>("Alice"),
 					get_account_id_from_seed::<sr25519::Public>("Bob"),
@@ -10,6 +10,18 @@ pub struct Cli {
 
 	#[structopt(long)]
 	pub author: Option<String>,
+
+	/// Number of threads used for mining. Set to 0 to leave the mining to external miners.
+	#[clap(long, default_value = "1")]
+	pub mining_threads: usize,
+
+	/// Listen for Stratum miners on the given address, e.g. `0.0.0.0:3333`. Needs `--validator`.
+	#[clap(long)]
+	pub stratum: Option<std::net::SocketAddr>,
+
+	/// Share difficulty Stratum connections start with. Never above the block difficulty.
+	#[clap(long, default_value = "1000")]
+	pub stratum_share_difficulty: u128,
 }
 
 #[derive(Debug, clap::Subcommand)]
@@ -50,4 +62,7 @@ pub enum Subcommand {
 	/// Try some command against runtime state. Note: `try-runtime` feature must be enabled.
 	#[cfg(not(feature = "try-runtime"))]
 	TryRuntime,
+
+	/// Simulate the difficulty retarget on synthetic hashrate scenarios.
+	SimulateDifficulty(crate::simulator::SimulateDifficultyCmd),
 }
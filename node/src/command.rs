@@ -160,12 +160,25 @@ pub fn run() -> sc_cli::Result<()> {
 		Some(Subcommand::TryRuntime) => Err("TryRuntime wasn't enabled when building the node. \
 				You can enable it with `--features try-runtime`."
 			.into()),
+		Some(Subcommand::SimulateDifficulty(cmd)) => cmd.run(),
 		None => {
 			let runner = cli.create_runner(&cli.run)?;
+			let stratum = cli.stratum.map(|listen| service::StratumConfig {
+				listen,
+				share_difficulty: cli.stratum_share_difficulty.into(),
+			});
 			runner.run_node_until_exit(|config| async move {
+				// Stratum miners are served the work of the mining worker of an authority
+				if stratum.is_some() && !config.role.is_authority() {
+					return Err(sc_cli::Error::Input(
+						"`--stratum` needs `--validator`, only authorities mine blocks".into(),
+					))
+				}
 				service::new_full(
 					config,
 					cli.author.as_ref().map(|s| s.as_str()),
+					cli.mining_threads,
+					stratum,
 					)
 				.map_err(sc_cli::Error::Service)
 			})
@@ -8,6 +8,8 @@ mod cli;
 mod command;
 mod command_helper;
 mod rpc;
+mod simulator;
+mod stratum;
 
 fn main() -> sc_cli::Result<()> {
 	command::run()
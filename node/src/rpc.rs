@@ -7,25 +7,46 @@
 
 use std::sync::Arc;
 
+use crate::stratum::ShareBook;
+use crain_pow::{miner::WorkSource, HashAlgorithm};
 use crain_runtime::{opaque::Block, AccountId, Balance, BlockNumber, Hash, Index};
 pub use sc_rpc_api::DenyUnsafe;
 use sc_transaction_pool_api::TransactionPool;
 use sp_api::ProvideRuntimeApi;
 use sp_block_builder::BlockBuilder;
 use sp_blockchain::{Error as BlockChainError, HeaderBackend, HeaderMetadata};
+use sp_consensus::SelectChain;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header as _},
+};
+
+pub mod difficulty;
+pub mod mining;
+pub mod rewards;
 
 /// Full client dependencies.
-pub struct FullDeps<C, P> {
+pub struct FullDeps<C, P, SC, W> {
 	/// The client instance to use.
 	pub client: Arc<C>,
+	/// Fork choice of the chain, picks the best block the RPCs answer for.
+	pub select_chain: SC,
 	/// Transaction pool instance.
 	pub pool: Arc<P>,
 	/// Whether to deny unsafe calls
 	pub deny_unsafe: DenyUnsafe,
+	/// Mining worker, if the node is mining
+	pub mining: Option<W>,
+	/// Hash algorithm of the chain
+	pub hasher: HashAlgorithm,
+	/// Shares of the stratum workers
+	pub shares: ShareBook,
 }
 
 /// Instantiate all full RPC extensions.
-pub fn create_full<C, P>(deps: FullDeps<C, P>) -> jsonrpc_core::IoHandler<sc_rpc::Metadata>
+pub fn create_full<C, P, SC, W>(
+	deps: FullDeps<C, P, SC, W>,
+) -> jsonrpc_core::IoHandler<sc_rpc::Metadata>
 where
 	C: ProvideRuntimeApi<Block>,
 	C: HeaderBackend<Block> + HeaderMetadata<Block, Error = BlockChainError> + 'static,
@@ -33,15 +54,22 @@ where
 	C::Api: substrate_frame_rpc_system::AccountNonceApi<Block, AccountId, Index>,
 	C::Api: pallet_transaction_payment_rpc::TransactionPaymentRuntimeApi<Block, Balance>,
 	C::Api: BlockBuilder<Block>,
+	C::Api: crain_primitives::RewardsApi<Block, AccountId, BlockNumber, Balance>,
+	C::Api: crain_primitives::DifficultyHistoryApi<Block, u64>,
 	C::Api: pallet_contracts_rpc::ContractsRuntimeApi<Block, AccountId, Balance, BlockNumber, Hash>,
 	P: TransactionPool + 'static,
+	SC: SelectChain<Block> + 'static,
+	W: WorkSource + Send + Sync + 'static,
 {
 	use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
 	use substrate_frame_rpc_system::{FullSystem, SystemApi};
 	use pallet_contracts_rpc::{Contracts, ContractsApi};
+	use difficulty::{DifficultyApi, DifficultyRpc};
+	use mining::{Mining, MiningApi};
+	use rewards::{Rewards, RewardsApi};
 
 	let mut io = jsonrpc_core::IoHandler::default();
-	let FullDeps { client, pool, deny_unsafe } = deps;
+	let FullDeps { client, select_chain, pool, deny_unsafe, mining, hasher, shares } = deps;
 
 	io.extend_with(SystemApi::to_delegate(FullSystem::new(client.clone(), pool, deny_unsafe)));
 
@@ -53,5 +81,34 @@ where
 	// `io.extend_with(YourRpcTrait::to_delegate(YourRpcStruct::new(ReferenceToClient, ...)));`
 	io.extend_with(Contracts::to_delegate(Contracts::new(client.clone())));
 
+	io.extend_with(RewardsApi::to_delegate(Rewards::new(client.clone(), select_chain.clone())));
+
+	io.extend_with(DifficultyApi::to_delegate(DifficultyRpc::new(client, select_chain)));
+
+	if let Some(worker) = mining {
+		io.extend_with(MiningApi::to_delegate(Mining::new(worker, hasher, shares)));
+	}
+
 	io
 }
+
+/// Block to answer a query for: the given one or else the best block of the fork choice rule.
+/// The client's own best block is the last one imported as best, which can lag behind the
+/// heaviest chain.
+pub fn block_at<B, SC>(select_chain: &SC, at: Option<B::Hash>) -> jsonrpc_core::Result<BlockId<B>>
+where
+	B: BlockT,
+	SC: SelectChain<B>,
+{
+	let hash = match at {
+		Some(hash) => hash,
+		None => futures::executor::block_on(select_chain.best_chain())
+			.map_err(|e| jsonrpc_core::Error {
+				code: jsonrpc_core::ErrorCode::InternalError,
+				message: "Unable to find the best block".into(),
+				data: Some(e.to_string().into()),
+			})?
+			.hash(),
+	};
+	Ok(BlockId::hash(hash))
+}
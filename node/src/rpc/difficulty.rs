@@ -0,0 +1,79 @@
+//! RPC methods about the difficulty retarget of the chain.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::rpc::block_at;
+use crain_primitives::{Difficulty, DifficultyAndTimestamp, DifficultyHistoryApi};
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use parity_scale_codec::Codec;
+use sp_api::{ApiError, ProvideRuntimeApi};
+use sp_consensus::SelectChain;
+use sp_runtime::traits::Block as BlockT;
+
+/// Difficulty RPC methods.
+#[rpc]
+pub trait DifficultyApi<BlockHash, Moment> {
+	/// Returns the difficulties and timestamps of the retarget window, from earliest to latest.
+	#[rpc(name = "crain_difficulty_window")]
+	fn window(&self, at: Option<BlockHash>) -> Result<Vec<DifficultyAndTimestamp<Moment>>>;
+
+	/// Returns the difficulty after the next block, if it is mined on target.
+	#[rpc(name = "crain_difficulty_predictedDifficulty")]
+	fn predicted_difficulty(&self, at: Option<BlockHash>) -> Result<Difficulty>;
+
+	/// Returns the network hashrate in hashes per second, estimated from the retarget window.
+	#[rpc(name = "crain_difficulty_hashrate")]
+	fn hashrate(&self, at: Option<BlockHash>) -> Result<Difficulty>;
+}
+
+/// Implementation of [`DifficultyApi`] on top of the runtime API of the client.
+pub struct DifficultyRpc<C, SC, B> {
+	client: Arc<C>,
+	select_chain: SC,
+	_marker: PhantomData<B>,
+}
+
+impl<C, SC, B> DifficultyRpc<C, SC, B> {
+	/// Creates a new instance of the difficulty RPC. Queries without a block are answered for
+	/// the best block of `select_chain`.
+	pub fn new(client: Arc<C>, select_chain: SC) -> Self {
+		Self { client, select_chain, _marker: PhantomData }
+	}
+}
+
+fn runtime_error(e: ApiError) -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(1),
+		message: "Unable to query the difficulty".into(),
+		data: Some(e.to_string().into()),
+	}
+}
+
+impl<C, SC, Block, Moment> DifficultyApi<<Block as BlockT>::Hash, Moment>
+	for DifficultyRpc<C, SC, Block>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: DifficultyHistoryApi<Block, Moment>,
+	SC: SelectChain<Block> + 'static,
+	Moment: Codec,
+{
+	fn window(
+		&self,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Vec<DifficultyAndTimestamp<Moment>>> {
+		let at = block_at(&self.select_chain, at)?;
+		self.client.runtime_api().difficulty_window(&at).map_err(runtime_error)
+	}
+
+	fn predicted_difficulty(&self, at: Option<<Block as BlockT>::Hash>) -> Result<Difficulty> {
+		let at = block_at(&self.select_chain, at)?;
+		self.client.runtime_api().predicted_difficulty(&at).map_err(runtime_error)
+	}
+
+	fn hashrate(&self, at: Option<<Block as BlockT>::Hash>) -> Result<Difficulty> {
+		let at = block_at(&self.select_chain, at)?;
+		self.client.runtime_api().hashrate(&at).map_err(runtime_error)
+	}
+}
@@ -0,0 +1,87 @@
+//! RPC methods that let miners outside of the node work on the node's blocks.
+
+use crate::stratum::{ShareBook, WorkerShares};
+use crain_pow::{miner::WorkSource, HashAlgorithm, Solution};
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use parity_scale_codec::Decode;
+use serde::{Deserialize, Serialize};
+use sp_core::{Bytes, H256, U256};
+use std::collections::BTreeMap;
+
+/// Work for an external miner.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Work {
+	/// Identifier of the job. Changes every time the node builds a new block to mine on.
+	pub job_id: u64,
+	/// Hash of the parent of the block being mined.
+	pub parent_hash: H256,
+	/// Hash of the block being mined, without the seal.
+	pub pre_hash: H256,
+	/// Difficulty the seal has to meet.
+	pub difficulty: U256,
+	/// PoW algorithm of the chain: `sha3`, `blake2b`, `keccak` or `memory-hard`.
+	pub algorithm: String,
+}
+
+/// Mining RPC methods.
+#[rpc]
+pub trait MiningApi {
+	/// Returns the current work, if the node has any.
+	#[rpc(name = "crain_mining_getWork")]
+	fn get_work(&self) -> Result<Option<Work>>;
+
+	/// Submits a SCALE encoded `crain_pow::Solution`. The node seals the block with its
+	/// author key. Returns `true` if the block was imported.
+	#[rpc(name = "crain_mining_submitWork")]
+	fn submit_work(&self, solution: Bytes) -> Result<bool>;
+
+	/// Returns the shares accepted from every stratum worker since the node started, by worker
+	/// name. Empty unless the stratum server runs.
+	#[rpc(name = "crain_mining_shares")]
+	fn shares(&self) -> Result<BTreeMap<String, WorkerShares>>;
+}
+
+/// Implementation of [`MiningApi`] on top of the node's mining worker.
+pub struct Mining<W> {
+	worker: W,
+	hasher: HashAlgorithm,
+	shares: ShareBook,
+}
+
+impl<W> Mining<W> {
+	/// Creates a new instance of the mining RPC.
+	pub fn new(worker: W, hasher: HashAlgorithm, shares: ShareBook) -> Self {
+		Self { worker, hasher, shares }
+	}
+}
+
+impl<W> MiningApi for Mining<W>
+where
+	W: WorkSource + Send + Sync + 'static,
+{
+	fn get_work(&self) -> Result<Option<Work>> {
+		Ok(self.worker.work().map(|job| Work {
+			job_id: job.id as u64,
+			parent_hash: job.parent,
+			pre_hash: job.pre_hash,
+			difficulty: job.difficulty,
+			algorithm: self.hasher.to_string(),
+		}))
+	}
+
+	fn submit_work(&self, solution: Bytes) -> Result<bool> {
+		let solution = Solution::decode(&mut &solution[..]).map_err(|e| RpcError {
+			code: ErrorCode::InvalidParams,
+			message: "Unable to decode the solution".into(),
+			data: Some(e.to_string().into()),
+		})?;
+
+		Ok(self.worker.submit(solution))
+	}
+
+	fn shares(&self) -> Result<BTreeMap<String, WorkerShares>> {
+		Ok(self.shares.snapshot())
+	}
+}
@@ -0,0 +1,64 @@
+//! RPC methods about the block rewards of the miners.
+
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::rpc::block_at;
+use crain_primitives::{ImmatureReward, RewardsApi as RewardsRuntimeApi};
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use parity_scale_codec::Codec;
+use sp_api::ProvideRuntimeApi;
+use sp_consensus::SelectChain;
+use sp_runtime::traits::Block as BlockT;
+
+/// Rewards RPC methods.
+#[rpc]
+pub trait RewardsApi<BlockHash, AccountId, BlockNumber, Balance> {
+	/// Returns the mined rewards of `account` that are still locked, from the oldest.
+	#[rpc(name = "crain_rewards_immatureRewards")]
+	fn immature_rewards(
+		&self,
+		account: AccountId,
+		at: Option<BlockHash>,
+	) -> Result<Vec<ImmatureReward<BlockNumber, Balance>>>;
+}
+
+/// Implementation of [`RewardsApi`] on top of the runtime API of the client.
+pub struct Rewards<C, SC, B> {
+	client: Arc<C>,
+	select_chain: SC,
+	_marker: PhantomData<B>,
+}
+
+impl<C, SC, B> Rewards<C, SC, B> {
+	/// Creates a new instance of the rewards RPC. Queries without a block are answered for the
+	/// best block of `select_chain`.
+	pub fn new(client: Arc<C>, select_chain: SC) -> Self {
+		Self { client, select_chain, _marker: PhantomData }
+	}
+}
+
+impl<C, SC, Block, AccountId, BlockNumber, Balance>
+	RewardsApi<<Block as BlockT>::Hash, AccountId, BlockNumber, Balance> for Rewards<C, SC, Block>
+where
+	Block: BlockT,
+	C: ProvideRuntimeApi<Block> + Send + Sync + 'static,
+	C::Api: RewardsRuntimeApi<Block, AccountId, BlockNumber, Balance>,
+	SC: SelectChain<Block> + 'static,
+	AccountId: Codec,
+	BlockNumber: Codec,
+	Balance: Codec,
+{
+	fn immature_rewards(
+		&self,
+		account: AccountId,
+		at: Option<<Block as BlockT>::Hash>,
+	) -> Result<Vec<ImmatureReward<BlockNumber, Balance>>> {
+		let at = block_at(&self.select_chain, at)?;
+		self.client.runtime_api().immature_rewards(&at, account).map_err(|e| RpcError {
+			code: ErrorCode::ServerError(1),
+			message: "Unable to query the immature rewards".into(),
+			data: Some(e.to_string().into()),
+		})
+	}
+}
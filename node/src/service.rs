@@ -7,32 +7,46 @@ use std::str::FromStr;
 use sc_service::{error::Error as ServiceError, Configuration, TaskManager};
 use sc_telemetry::{Telemetry, TelemetryWorker};
 use log::*;
-use sp_core::{U256, H256};
-use std::thread;
-use crain_pow::*;
+use sp_core::H256;
 use parity_scale_codec::Encode;
 use sp_core::Pair;
 use std::path::PathBuf;
 use async_trait::async_trait;
-use crain_pow::Sha3Algorithm;
-use sp_core::crypto::{Ss58AddressFormat, Ss58AddressFormatRegistry, Ss58Codec, UncheckedFrom};
+use crain_pow::{ChainPowAlgorithm, HashAlgorithm};
+use crain_primitives::PowAlgorithmApi;
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_blockchain::HeaderBackend;
+use sp_runtime::generic::BlockId;
+use sp_core::crypto::{
+	Public as _, Ss58AddressFormat, Ss58AddressFormatRegistry, Ss58Codec, UncheckedFrom,
+};
 use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
 use sp_runtime::traits::Block as BlockT;
 use std::{sync::Arc, time::Duration};
+pub use crate::stratum::StratumConfig;
 
 
-pub struct InherentDataProvidersBuilder;
+// Inherents of the blocks: the timestamp and the uncles
+pub struct InherentDataProvidersBuilder {
+	client: Arc<FullClient>,
+}
 
 #[async_trait]
 impl sp_inherents::CreateInherentDataProviders<Block, ()> for InherentDataProvidersBuilder {
-	type InherentDataProviders = sp_timestamp::InherentDataProvider;
+	type InherentDataProviders = (
+		sp_timestamp::InherentDataProvider,
+		sp_authorship::InherentDataProvider<<Block as BlockT>::Header>,
+	);
 
 	async fn create_inherent_data_providers(
 		&self,
-		_parent: <Block as BlockT>::Hash,
+		parent: <Block as BlockT>::Hash,
 		_extra_args: (),
 	) -> Result<Self::InherentDataProviders, Box<dyn std::error::Error + Send + Sync>> {
-		Ok(sp_timestamp::InherentDataProvider::from_system_time())
+		let uncles =
+			sc_consensus_uncles::create_uncles_inherent_data_provider(&*self.client, parent)?;
+
+		Ok((sp_timestamp::InherentDataProvider::from_system_time(), uncles))
 	}
 }
 
@@ -59,7 +73,21 @@ impl sc_executor::NativeExecutionDispatch for ExecutorDispatch {
 pub(crate) type FullClient =
 	sc_service::TFullClient<Block, RuntimeApi, NativeElseWasmExecutor<ExecutorDispatch>>;
 type FullBackend = sc_service::TFullBackend<Block>;
-type FullSelectChain = sc_consensus::LongestChain<FullBackend, Block>;
+// PoW fork choice, the chain with the most work is the best one
+type FullSelectChain = crain_pow::HeaviestChain<FullBackend, Block>;
+
+// PoW algorithm of the chain, kept by the runtime. Runtimes from before `PowAlgorithmApi`
+// only knew Sha3.
+fn pow_algorithm(client: &FullClient) -> Result<HashAlgorithm, ServiceError> {
+	let best = BlockId::Hash(client.info().best_hash);
+	let api = client.runtime_api();
+	let error = |e| ServiceError::Other(format!("Fetching the PoW algorithm failed: {:?}", e));
+
+	if !api.has_api::<dyn PowAlgorithmApi<Block>>(&best).map_err(error)? {
+		return Ok(HashAlgorithm::default())
+	}
+	api.pow_algorithm(&best).map_err(error)
+}
 
 
 /// Returns most parts of a service. Not enough to run a full chain,
@@ -79,7 +107,7 @@ pub fn new_partial(
 				Arc<FullClient>,
 				FullClient,
 				FullSelectChain,
-				Sha3Algorithm<FullClient>,
+				ChainPowAlgorithm<Block, FullClient>,
 				sp_consensus::CanAuthorWithNativeVersion<
 					sc_service::LocalCallExecutor<
 						Block,
@@ -89,6 +117,7 @@ pub fn new_partial(
 				>,
 				InherentDataProvidersBuilder,
 			>,
+			ChainPowAlgorithm<Block, FullClient>,
 			Option<Telemetry>,
 		),
 	>,
@@ -130,7 +159,7 @@ pub fn new_partial(
 		telemetry
 	});
 
-	let select_chain = sc_consensus::LongestChain::new(backend.clone());
+	let select_chain = crain_pow::HeaviestChain::new(backend.clone());
 
 	let transaction_pool = sc_transaction_pool::BasicPool::new_full(
 		config.transaction_pool.clone(),
@@ -140,7 +169,9 @@ pub fn new_partial(
 		client.clone(),
 	);
 
-	let algorithm = Sha3Algorithm::new(client.clone());
+	let hasher = pow_algorithm(&client)?;
+	let algorithm =
+		ChainPowAlgorithm::new(client.clone(), hasher).with_metrics(config.prometheus_registry())?;
 
 	let pow_block_import = sc_consensus_pow::PowBlockImport::new(
 		client.clone(),
@@ -148,7 +179,7 @@ pub fn new_partial(
 		algorithm.clone(),
 		0,
 		select_chain.clone(),
-		InherentDataProvidersBuilder,
+		InherentDataProvidersBuilder { client: client.clone() },
 		sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone()), 
 		);
 
@@ -172,7 +203,7 @@ pub fn new_partial(
 		select_chain,
 		import_queue,
 		transaction_pool,
-		other: (pow_block_import, telemetry),
+		other: (pow_block_import, algorithm, telemetry),
 		
 	})
 }
@@ -183,11 +214,10 @@ pub fn decode_author(
 	keystore_path: Option<PathBuf>,
 ) -> Result<crain_pow::app::Public, String> {
 	if let Some(author) = author {
-		if author.starts_with("0x") {
-			Ok(crain_pow::app::Public::unchecked_from(
+		let address = if author.starts_with("0x") {
+			crain_pow::app::Public::unchecked_from(
 				H256::from_str(&author[2..]).map_err(|_| "Invalid author account".to_string())?,
 			)
-			.into())
 		} else {
 			// This line compiles if sp_core::crypto std feature is enabled
 			let (address, version) = crain_pow::app::Public::from_ss58check_with_version(author)
@@ -195,8 +225,19 @@ pub fn decode_author(
 			if version != Ss58AddressFormat::from(Ss58AddressFormatRegistry::BareSr25519Account) {
 				return Err("Invalid author version".to_string());
 			}
-			Ok(address)
+			address
+		};
+
+		// Blocks are sealed with the author key, so the node must be able to sign with it
+		if !SyncCryptoStore::has_keys(&*keystore, &[(address.to_raw_vec(), crain_pow::app::ID)]) {
+			return Err(format!(
+				"The key of the author {} is not in the keystore, insert it with the `key insert` \
+				 subcommand and the `crn1` key type",
+				author
+			));
 		}
+
+		Ok(address)
 	} else {
 		info!("The node is configured for mining, but no author key is provided.");
 
@@ -227,7 +268,12 @@ pub fn decode_author(
 }
 
 /// Builds a new service for a full client.
-pub fn new_full(config: Configuration, author: Option<&str>) -> Result<TaskManager, ServiceError> {
+pub fn new_full(
+	config: Configuration,
+	author: Option<&str>,
+	mining_threads: usize,
+	stratum: Option<StratumConfig>,
+) -> Result<TaskManager, ServiceError> {
 	let sc_service::PartialComponents {
 		client,
 		backend,
@@ -236,7 +282,7 @@ pub fn new_full(config: Configuration, author: Option<&str>) -> Result<TaskManag
 		select_chain,
 		import_queue,
 		transaction_pool,
-		other: (pow_block_import, 
+		other: (pow_block_import, algorithm,
 			mut telemetry),
 	} = new_partial(&config)?;
 
@@ -265,42 +311,18 @@ pub fn new_full(config: Configuration, author: Option<&str>) -> Result<TaskManag
 	let role = config.role.clone();
 	let prometheus_registry = config.prometheus_registry().cloned();
 
-	let rpc_extensions_builder = {
-		let client = client.clone();
-		let pool = transaction_pool.clone();
-
-		Box::new(move |deny_unsafe, _| {
-			let deps =
-				crate::rpc::FullDeps { client: client.clone(), pool: pool.clone(), deny_unsafe };
-
-			Ok(crate::rpc::create_full(deps))
-		})
-	};
-
 	let keystore_path = config.keystore.path().map(|p| p.to_owned());
+	let hasher = pow_algorithm(&client)?;
 
-	let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
-		config,
-		client: client.clone(),
-		backend,
-		task_manager: &mut task_manager,
-		keystore: keystore_container.sync_keystore(),
-		transaction_pool: transaction_pool.clone(),
-		rpc_extensions_builder,
-		network: network.clone(),
-		system_rpc_tx,
-		telemetry: telemetry.as_mut(),
-	})?;
-
-	if role.is_authority() {
+	// The mining worker is created before the RPCs so that external miners can use it too
+	let mining_worker = if role.is_authority() {
 
-		let algorithm = Sha3Algorithm::new(client.clone());
 		let author = decode_author(author, keystore_container.sync_keystore(), keystore_path)?;
 		
 		let proposer_factory = sc_basic_authorship::ProposerFactory::new(
 			task_manager.spawn_handle(),
 			client.clone(),
-			transaction_pool,
+			transaction_pool.clone(),
 			prometheus_registry.as_ref(),
 			telemetry.as_ref().map(|x| x.handle()),
 		);
@@ -309,16 +331,16 @@ pub fn new_full(config: Configuration, author: Option<&str>) -> Result<TaskManag
 		let can_author_with = sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone());
 
 
-		let (_worker, worker_task) = sc_consensus_pow::start_mining_worker(
+		let (worker, worker_task) = sc_consensus_pow::start_mining_worker(
 			Box::new(pow_block_import.clone()),
 			client.clone(),
 			select_chain.clone(),
-			algorithm,
+			algorithm.clone(),
 			proposer_factory,
 			network.clone(),
 			network.clone(),
 			Some(author.encode()), // Include authorship into block
-			InherentDataProvidersBuilder,
+			InherentDataProvidersBuilder { client: client.clone() },
 			// Time to wait for a new block before starting to mine a new one
 			Duration::new(10, 0),
 			// How long to take to actually build the block (i.e. executing extrinsics)
@@ -332,32 +354,67 @@ pub fn new_full(config: Configuration, author: Option<&str>) -> Result<TaskManag
 			.spawn_essential_handle()
 			.spawn_blocking("pow", Some("block-authoring"), worker_task);
 
-		// Start Mining
-		// (from recipes a bit modified)
-		let mut nonce: U256 = U256::from(0);
-		thread::spawn(move || loop {
-			let worker = _worker.clone();
-			let metadata = worker.metadata();
-			if let Some(metadata) = metadata {
-				let compute = Compute {
-					difficulty: metadata.difficulty,
-					pre_hash: metadata.pre_hash,
-					nonce,
-				};
-				let seal = compute.compute();
-				if hash_meets_difficulty(&seal.work, seal.difficulty) {
-					nonce = U256::from(0);
-					let _ = futures::executor::block_on(worker.submit(seal.encode()));
-				} else {
-					nonce = nonce.saturating_add(U256::from(1));
-					if nonce == U256::MAX {
-						nonce = U256::from(0);
-					}
-				}
-			} else {
-				thread::sleep(Duration::new(1, 0));
-			}
-		});
+		Some(crain_pow::miner::SigningWorker::new(
+			worker,
+			author,
+			keystore_container.sync_keystore(),
+		))
+	} else {
+		None
+	};
+
+	// Shares of the stratum workers, also readable over RPC
+	let shares = crate::stratum::ShareBook::default();
+
+	let rpc_extensions_builder = {
+		let client = client.clone();
+		let select_chain = select_chain.clone();
+		let pool = transaction_pool.clone();
+		let mining = mining_worker.clone();
+		let shares = shares.clone();
+
+		Box::new(move |deny_unsafe, _| {
+			let deps = crate::rpc::FullDeps {
+				client: client.clone(),
+				select_chain: select_chain.clone(),
+				pool: pool.clone(),
+				deny_unsafe,
+				mining: mining.clone(),
+				hasher,
+				shares: shares.clone(),
+			};
+
+			Ok(crate::rpc::create_full(deps))
+		})
+	};
+
+	let _rpc_handlers = sc_service::spawn_tasks(sc_service::SpawnTasksParams {
+		config,
+		client: client.clone(),
+		backend,
+		task_manager: &mut task_manager,
+		keystore: keystore_container.sync_keystore(),
+		transaction_pool: transaction_pool.clone(),
+		rpc_extensions_builder,
+		network: network.clone(),
+		system_rpc_tx,
+		telemetry: telemetry.as_mut(),
+	})?;
+
+	if let Some(worker) = mining_worker {
+		if let Some(stratum) = stratum {
+			task_manager.spawn_handle().spawn(
+				"stratum",
+				Some("block-authoring"),
+				crate::stratum::run(stratum, worker.clone(), hasher, shares),
+			);
+		}
+
+		// Start Mining, unless all the mining is left to external miners. The threads share the
+		// datasets of the memory-hard algorithm with the import queue.
+		if mining_threads > 0 {
+			crain_pow::miner::start_mining_threads(worker, algorithm, mining_threads);
+		}
 	}
 
 	network_starter.start_network();
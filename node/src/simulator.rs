@@ -0,0 +1,312 @@
+//! `simulate-difficulty` subcommand: runs the retarget of `pallet-difficulty` on synthetic
+//! hashrate scenarios and prints the block times and difficulties it gives.
+
+use crain_primitives::{
+	Difficulty, BLOCK_TIME, CLAMP_FACTOR, DIFFICULTY_ADJUST_WINDOW, DIFFICULTY_DAMP_FACTOR,
+	DIFFICULTY_HALF_LIFE, MAX_DIFFICULTY, MIN_DIFFICULTY,
+};
+use pallet_difficulty::{
+	DampClamp, DifficultyAlgorithm, Exponential, Lwma, RetargetParams, WindowState, WindowSummary,
+};
+use serde::Serialize;
+use std::io::Write;
+
+/// Hashrate scenario of a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum Scenario {
+	/// The hashrate stays the same.
+	Constant,
+	/// The hashrate is multiplied by `--factor` at `--at`.
+	Step,
+	/// Miners with `--factor` times the hashrate join for `--period` blocks and leave for as long.
+	HashAndRun,
+	/// Miners with `--share` percent of the hashrate date their blocks `--drift` milliseconds
+	/// in the future.
+	TimestampManipulation,
+}
+
+/// Difficulty algorithm of a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum Algorithm {
+	/// `pallet_difficulty::DampClamp`, the algorithm of the runtime.
+	DampClamp,
+	/// `pallet_difficulty::Lwma`.
+	Lwma,
+	/// `pallet_difficulty::Exponential` with a `--half-life` half-life.
+	Exponential,
+}
+
+/// Output format of a simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum Format {
+	/// A header line, then a line per block.
+	Csv,
+	/// An array with an object per block.
+	Json,
+}
+
+/// Simulate the difficulty retarget on a synthetic hashrate scenario.
+#[derive(Debug, Clone, clap::Parser)]
+pub struct SimulateDifficultyCmd {
+	/// Hashrate scenario.
+	#[clap(long, arg_enum, default_value = "step")]
+	pub scenario: Scenario,
+
+	/// Difficulty algorithm.
+	#[clap(long, arg_enum, default_value = "damp-clamp")]
+	pub algorithm: Algorithm,
+
+	/// Output format.
+	#[clap(long, arg_enum, default_value = "csv")]
+	pub format: Format,
+
+	/// Number of blocks to simulate.
+	#[clap(long, default_value = "1000")]
+	pub blocks: u32,
+
+	/// Hashrate at the start, in hashes per second. The chain starts at the difficulty
+	/// that gives the target block time with it.
+	#[clap(long, default_value = "1000000")]
+	pub hashrate: u128,
+
+	/// Factor the hashrate changes by in the `step` and `hash-and-run` scenarios.
+	#[clap(long, default_value = "10")]
+	pub factor: f64,
+
+	/// Block of the hashrate change in the `step` scenario.
+	#[clap(long, default_value = "500")]
+	pub at: u32,
+
+	/// Blocks the miners stay and leave for in the `hash-and-run` scenario.
+	#[clap(long, default_value = "60")]
+	pub period: u32,
+
+	/// Percent of the hashrate dating its blocks in the future in the
+	/// `timestamp-manipulation` scenario.
+	#[clap(long, default_value = "30")]
+	pub share: u8,
+
+	/// Milliseconds blocks are dated in the future in the `timestamp-manipulation` scenario.
+	#[clap(long, default_value = "120000")]
+	pub drift: u128,
+
+	/// Target block time in milliseconds.
+	#[clap(long, default_value_t = BLOCK_TIME as u128)]
+	pub block_time: u128,
+
+	/// Number of blocks in the retarget window.
+	#[clap(long, default_value_t = DIFFICULTY_ADJUST_WINDOW as u32)]
+	pub window: u32,
+
+	/// Dampening factor of the damp and clamp algorithm.
+	#[clap(long, default_value_t = DIFFICULTY_DAMP_FACTOR)]
+	pub damp: u128,
+
+	/// Clamping factor of the damp and clamp algorithm.
+	#[clap(long, default_value_t = CLAMP_FACTOR)]
+	pub clamp: u128,
+
+	/// Lowest difficulty.
+	#[clap(long, default_value_t = MIN_DIFFICULTY)]
+	pub min_difficulty: u128,
+
+	/// Highest difficulty.
+	#[clap(long, default_value_t = MAX_DIFFICULTY)]
+	pub max_difficulty: u128,
+
+	/// Half-life of the exponential algorithm in blocks.
+	#[clap(long, default_value_t = DIFFICULTY_HALF_LIFE)]
+	pub half_life: u32,
+
+	/// Seed of the random solve times.
+	#[clap(long, default_value = "42")]
+	pub seed: u64,
+}
+
+/// A simulated block.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulatedBlock {
+	/// Height of the block.
+	pub number: u32,
+	/// Timestamp of the block, in milliseconds.
+	pub timestamp: u128,
+	/// Time since the parent block by their timestamps, in milliseconds.
+	pub block_time: u128,
+	/// Time the block really took to mine, in milliseconds.
+	pub solve_time: u128,
+	/// Difficulty the block was mined at.
+	pub difficulty: u128,
+	/// Hashrate that mined the block, in hashes per second.
+	pub hashrate: u128,
+}
+
+// Xorshift generator, so a seed always gives the same series
+struct Rng(u64);
+
+impl Rng {
+	// Uniform in (0, 1]
+	fn next(&mut self) -> f64 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		((self.0 >> 11) + 1) as f64 / (1u64 << 53) as f64
+	}
+}
+
+impl SimulateDifficultyCmd {
+	/// Run the simulation and print the blocks.
+	pub fn run(&self) -> sc_cli::Result<()> {
+		// The same checks as the integrity test of `pallet_difficulty`
+		if self.damp == 0 || self.clamp == 0 {
+			return Err("`--damp` and `--clamp` must be at least 1".to_string().into())
+		}
+		if self.window == 0 {
+			return Err("`--window` must not be 0".to_string().into())
+		}
+		if self.min_difficulty > self.max_difficulty {
+			return Err("`--min-difficulty` must not be higher than `--max-difficulty`"
+				.to_string()
+				.into())
+		}
+
+		let blocks = self.simulate();
+		let stdout = std::io::stdout();
+		let mut out = stdout.lock();
+
+		match self.format {
+			Format::Csv => {
+				writeln!(out, "number,timestamp,block_time,solve_time,difficulty,hashrate")?;
+				for block in &blocks {
+					writeln!(
+						out,
+						"{},{},{},{},{},{}",
+						block.number,
+						block.timestamp,
+						block.block_time,
+						block.solve_time,
+						block.difficulty,
+						block.hashrate,
+					)?;
+				}
+			},
+			Format::Json => {
+				serde_json::to_writer_pretty(&mut out, &blocks)
+					.map_err(|e| format!("Unable to write the blocks: {}", e))?;
+				writeln!(out)?;
+			},
+		}
+
+		Ok(())
+	}
+
+	/// Mine the blocks of the scenario, retargeting after every one of them.
+	pub fn simulate(&self) -> Vec<SimulatedBlock> {
+		let params = RetargetParams {
+			target_block_time: self.block_time,
+			damp_factor: self.damp,
+			clamp_factor: self.clamp,
+			half_life: self.half_life,
+			min_difficulty: self.min_difficulty,
+			max_difficulty: self.max_difficulty,
+		};
+		let initial_difficulty =
+			params.bound(Difficulty::from(self.hashrate.saturating_mul(self.block_time) / 1000));
+
+		let mut rng = Rng(self.seed.max(1));
+		let mut ring = vec![(Difficulty::zero(), 0u128); self.window as usize];
+		let mut state = WindowState::default();
+		let mut difficulty = initial_difficulty;
+		let mut now = 0u128;
+		let mut timestamp = 0u128;
+		let mut blocks = Vec::with_capacity(self.blocks as usize);
+
+		for number in 1..=self.blocks {
+			let hashrate = self.hashrate_at(number);
+			// Exponential solve time with the mean `difficulty / hashrate`
+			let mean = difficulty.low_u128() as f64 * 1000.0 / hashrate.max(1) as f64;
+			let solve_time = (-rng.next().ln() * mean) as u128;
+			now += solve_time;
+
+			// Blocks are never dated before their parent
+			let prev_timestamp = timestamp;
+			timestamp = now.max(timestamp);
+			if self.scenario == Scenario::TimestampManipulation &&
+				rng.next() * 100.0 < self.share as f64
+			{
+				timestamp = now + self.drift;
+			}
+
+			blocks.push(SimulatedBlock {
+				number,
+				timestamp,
+				block_time: timestamp.saturating_sub(prev_timestamp),
+				solve_time,
+				difficulty: difficulty.low_u128(),
+				hashrate,
+			});
+
+			// The same window bookkeeping as `on_timestamp_set` of `pallet_difficulty`
+			let slot =
+				state.advance(difficulty, timestamp, self.window, |slot| Some(ring[slot as usize]));
+			if let Some(slot) = slot {
+				ring[slot as usize] = (difficulty, timestamp);
+			}
+			let summary = state.summary(self.window, initial_difficulty, self.block_time);
+			difficulty = self.next_difficulty(&summary, &params);
+		}
+
+		blocks
+	}
+
+	// Hashrate mining the block at `number`
+	fn hashrate_at(&self, number: u32) -> u128 {
+		let boosted = (self.hashrate as f64 * self.factor) as u128;
+		match self.scenario {
+			Scenario::Step if number >= self.at => boosted,
+			Scenario::HashAndRun if (number / self.period.max(1)) % 2 == 1 => boosted,
+			_ => self.hashrate,
+		}
+	}
+
+	fn next_difficulty(&self, summary: &WindowSummary, params: &RetargetParams) -> Difficulty {
+		match self.algorithm {
+			Algorithm::DampClamp => DampClamp::next_difficulty(summary, params),
+			Algorithm::Lwma => Lwma::next_difficulty(summary, params),
+			Algorithm::Exponential => Exponential::next_difficulty(summary, params),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use clap::Parser;
+
+	fn cmd(args: &[&str]) -> SimulateDifficultyCmd {
+		let args = std::iter::once("simulate-difficulty").chain(args.iter().copied());
+		SimulateDifficultyCmd::parse_from(args)
+	}
+
+	fn average_difficulty(blocks: &[SimulatedBlock]) -> u128 {
+		blocks.iter().map(|block| block.difficulty).sum::<u128>() / blocks.len() as u128
+	}
+
+	#[test]
+	fn same_seed_gives_same_series() {
+		let args = ["--scenario", "hash-and-run", "--blocks", "200"];
+		assert_eq!(cmd(&args).simulate(), cmd(&args).simulate());
+	}
+
+	#[test]
+	fn difficulty_follows_a_step() {
+		for algorithm in ["damp-clamp", "lwma", "exponential"] {
+			let blocks =
+				cmd(&["--algorithm", algorithm, "--blocks", "1000", "--at", "500"]).simulate();
+
+			let before = average_difficulty(&blocks[400..500]);
+			let after = average_difficulty(&blocks[900..]);
+			assert!(after > 5 * before, "{}: {} then {}", algorithm, before, after);
+		}
+	}
+}
@@ -0,0 +1,683 @@
+//! Stratum-like TCP mining server.
+//!
+//! Speaks line based JSON in the spirit of Stratum V1 so that pool software and existing
+//! miner tools can work on the node's blocks:
+//! - `mining.subscribe` returns the subscription id and the extranonce of the connection
+//! - `mining.authorize` registers the worker name the shares are booked to
+//! - `mining.suggest_difficulty` asks for another share difficulty
+//! - `mining.submit` with `[worker, job_id, nonce]` submits a share
+//!
+//! The server sends `mining.set_difficulty` with the share difficulty of the connection and
+//! `mining.notify` with `[job_id, pre_hash, difficulty, clean_jobs]` for every new job.
+//!
+//! Miners hash `crain_pow::Compute` with the block `difficulty` of the job and the hash
+//! algorithm of the chain, exactly like the node does. The top 64 bits of every nonce must be
+//! the extranonce of the connection so that different connections never search the same
+//! nonces. Shares only have to meet the share difficulty, which is never above the block
+//! difficulty. Shares that also meet the block difficulty are sealed with the node's author key
+//! and submitted as blocks.
+//!
+//! Accepted shares are counted per worker name, the `crain_mining_shares` RPC returns the
+//! counts.
+//!
+//! Chains using the memory-hard algorithm are not served: its datasets are built from the chain,
+//! which the miners do not have.
+
+use crain_pow::{
+	hash_meets_difficulty,
+	miner::{MiningJob, WorkSource},
+	Compute, HashAlgorithm, PowHasher,
+};
+use log::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sp_core::U256;
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	net::SocketAddr,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex, MutexGuard,
+	},
+	time::Duration,
+};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+	net::{tcp::OwnedWriteHalf, TcpListener, TcpStream},
+	sync::watch,
+};
+
+const LOG_TARGET: &str = "stratum";
+
+// How often the server checks for a new job
+const JOB_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Most worker names the share book keeps, further names are refused when they authorize
+const MAX_WORKERS: usize = 1024;
+
+// Most shares a connection can submit for one job. Bounds the nonces kept to reject duplicate
+// shares, a miner hitting it has to suggest a higher share difficulty.
+const MAX_SHARES_PER_JOB: usize = 4096;
+
+// Stratum error codes
+const ERROR_OTHER: i64 = 20;
+const ERROR_JOB_NOT_FOUND: i64 = 21;
+const ERROR_DUPLICATE_SHARE: i64 = 22;
+const ERROR_LOW_DIFFICULTY: i64 = 23;
+const ERROR_UNAUTHORIZED: i64 = 24;
+const ERROR_NOT_SUBSCRIBED: i64 = 25;
+
+/// Configuration of the stratum server.
+#[derive(Clone, Debug)]
+pub struct StratumConfig {
+	/// Address to listen on.
+	pub listen: SocketAddr,
+	/// Share difficulty new connections start with.
+	pub share_difficulty: U256,
+}
+
+/// Shares accepted from a worker since the node started.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerShares {
+	/// Number of accepted shares.
+	pub shares: u64,
+	/// Sum of the share difficulties of the accepted shares.
+	pub difficulty: U256,
+}
+
+/// Accepted shares per worker name, shared by the connections of the stratum server and the
+/// mining RPC.
+#[derive(Clone, Default)]
+pub struct ShareBook(Arc<Mutex<HashMap<String, WorkerShares>>>);
+
+impl ShareBook {
+	/// Shares of every worker that authorized so far, by name.
+	pub fn snapshot(&self) -> BTreeMap<String, WorkerShares> {
+		self.lock().iter().map(|(name, shares)| (name.clone(), *shares)).collect()
+	}
+
+	// Make room for the worker, `false` if the book is full
+	fn register(&self, name: &str) -> bool {
+		let mut book = self.lock();
+		if book.contains_key(name) {
+			return true
+		}
+		if book.len() >= MAX_WORKERS {
+			return false
+		}
+		book.insert(name.to_string(), WorkerShares::default());
+		true
+	}
+
+	// Book an accepted share of the worker, returns the worker's new totals
+	fn record(&self, name: &str, difficulty: U256) -> WorkerShares {
+		let mut book = self.lock();
+		let shares = book.entry(name.to_string()).or_default();
+		shares.shares = shares.shares.saturating_add(1);
+		shares.difficulty = shares.difficulty.saturating_add(difficulty);
+		*shares
+	}
+
+	fn lock(&self) -> MutexGuard<HashMap<String, WorkerShares>> {
+		self.0.lock().expect("Share book lock is never poisoned; qed")
+	}
+}
+
+#[derive(Deserialize)]
+struct Request {
+	#[serde(default)]
+	id: Value,
+	method: String,
+	#[serde(default)]
+	params: Vec<Value>,
+}
+
+type Reply = Result<Value, (i64, &'static str)>;
+
+// State of a single miner connection
+struct Connection<W> {
+	worker: W,
+	hasher: &'static (dyn PowHasher + Sync),
+	shares: ShareBook,
+	extranonce: u64,
+	subscribed: bool,
+	authorized: Option<String>,
+	share_difficulty: U256,
+	job: Option<MiningJob>,
+	// Nonces already submitted for the current job, at most `MAX_SHARES_PER_JOB`
+	nonces: HashSet<U256>,
+}
+
+/// Run the stratum server until the node shuts down. Accepted shares are booked to `shares`.
+pub async fn run<W>(config: StratumConfig, worker: W, hasher: HashAlgorithm, shares: ShareBook)
+where
+	W: WorkSource + Clone + Send + Sync + 'static,
+{
+	let hasher = match hasher.plain_hasher() {
+		Some(plain) => plain,
+		None => {
+			error!(target: LOG_TARGET, "Stratum can not serve the {} algorithm", hasher);
+			return
+		},
+	};
+
+	let listener = match TcpListener::bind(config.listen).await {
+		Ok(listener) => listener,
+		Err(e) => {
+			error!(target: LOG_TARGET, "Unable to listen on {}: {}", config.listen, e);
+			return
+		},
+	};
+	info!(target: LOG_TARGET, "Stratum server listening on {}", config.listen);
+
+	let (jobs_tx, jobs_rx) = watch::channel(worker.work());
+	tokio::spawn(watch_jobs(worker.clone(), jobs_tx));
+
+	let next_extranonce = AtomicU64::new(0);
+
+	loop {
+		let (socket, address) = match listener.accept().await {
+			Ok(accepted) => accepted,
+			Err(e) => {
+				warn!(target: LOG_TARGET, "Unable to accept a connection: {}", e);
+				continue
+			},
+		};
+		debug!(target: LOG_TARGET, "New connection from {}", address);
+
+		let connection = Connection::new(
+			worker.clone(),
+			hasher,
+			shares.clone(),
+			next_extranonce.fetch_add(1, Ordering::Relaxed),
+			config.share_difficulty,
+		);
+		let jobs = jobs_rx.clone();
+		tokio::spawn(async move {
+			if let Err(e) = connection.serve(socket, jobs).await {
+				debug!(target: LOG_TARGET, "Connection from {} closed: {}", address, e);
+			}
+		});
+	}
+}
+
+// Publish every new job of the worker
+async fn watch_jobs<W: WorkSource>(worker: W, jobs: watch::Sender<Option<MiningJob>>) {
+	let mut interval = tokio::time::interval(JOB_POLL_INTERVAL);
+	let mut job_id = worker.job_id();
+	loop {
+		interval.tick().await;
+		let latest = worker.job_id();
+		if latest != job_id {
+			job_id = latest;
+			if jobs.send(worker.work()).is_err() {
+				return
+			}
+		}
+	}
+}
+
+impl<W> Connection<W>
+where
+	W: WorkSource + Clone + Send + Sync + 'static,
+{
+	fn new(
+		worker: W,
+		hasher: &'static (dyn PowHasher + Sync),
+		shares: ShareBook,
+		extranonce: u64,
+		share_difficulty: U256,
+	) -> Self {
+		Self {
+			worker,
+			hasher,
+			shares,
+			extranonce,
+			subscribed: false,
+			authorized: None,
+			share_difficulty,
+			job: None,
+			nonces: HashSet::new(),
+		}
+	}
+
+	async fn serve(
+		mut self,
+		socket: TcpStream,
+		mut jobs: watch::Receiver<Option<MiningJob>>,
+	) -> std::io::Result<()> {
+		let (reader, mut writer) = socket.into_split();
+		let mut lines = BufReader::new(reader).lines();
+
+		loop {
+			tokio::select! {
+				line = lines.next_line() => {
+					let line = match line? {
+						Some(line) => line,
+						None => return Ok(()),
+					};
+					if line.trim().is_empty() {
+						continue
+					}
+					let response = match serde_json::from_str::<Request>(&line) {
+						Ok(request) => {
+							let reply = self.handle(&request.method, &request.params).await;
+							let response = match reply {
+								Ok(result) =>
+									json!({ "id": request.id, "result": result, "error": null }),
+								Err((code, message)) => json!({
+									"id": request.id,
+									"result": null,
+									"error": [code, message, null],
+								}),
+							};
+							send(&mut writer, response).await?;
+							// Hand out work right after the subscription
+							if request.method == "mining.subscribe" && self.subscribed {
+								let job = jobs.borrow().clone();
+								self.notify(&mut writer, job).await?;
+							}
+							continue
+						},
+						Err(_) => json!({
+							"id": null,
+							"result": null,
+							"error": [ERROR_OTHER, "Invalid request", null],
+						}),
+					};
+					send(&mut writer, response).await?;
+				},
+				changed = jobs.changed() => {
+					if changed.is_err() {
+						return Ok(())
+					}
+					if self.subscribed {
+						let job = jobs.borrow().clone();
+						self.notify(&mut writer, job).await?;
+					}
+				},
+			}
+		}
+	}
+
+	async fn handle(&mut self, method: &str, params: &[Value]) -> Reply {
+		match method {
+			"mining.subscribe" => {
+				self.subscribed = true;
+				let subscription = format!("{:016x}", self.extranonce);
+				Ok(json!([
+					[["mining.set_difficulty", subscription], ["mining.notify", subscription]],
+					format!("{:016x}", self.extranonce),
+					24,
+				]))
+			},
+			"mining.authorize" => {
+				let name = params
+					.get(0)
+					.and_then(Value::as_str)
+					.ok_or((ERROR_OTHER, "Worker name expected"))?;
+				if !self.shares.register(name) {
+					return Err((ERROR_OTHER, "Too many workers"))
+				}
+				self.authorized = Some(name.to_string());
+				Ok(json!(true))
+			},
+			"mining.suggest_difficulty" => {
+				let difficulty =
+					params.get(0).and_then(parse_u256).ok_or((ERROR_OTHER, "Difficulty expected"))?;
+				self.share_difficulty = difficulty.max(U256::one());
+				Ok(json!(true))
+			},
+			"mining.submit" => self.submit(params).await,
+			_ => Err((ERROR_OTHER, "Unknown method")),
+		}
+	}
+
+	async fn submit(&mut self, params: &[Value]) -> Reply {
+		if !self.subscribed {
+			return Err((ERROR_NOT_SUBSCRIBED, "Not subscribed"))
+		}
+		let name = match (&self.authorized, params.get(0).and_then(Value::as_str)) {
+			(Some(authorized), Some(name)) if authorized == name => name.to_string(),
+			_ => return Err((ERROR_UNAUTHORIZED, "Unauthorized worker")),
+		};
+		let job = match (&self.job, params.get(1).and_then(Value::as_str)) {
+			(Some(job), Some(job_id)) if format!("{:x}", job.id) == job_id => job.clone(),
+			_ => return Err((ERROR_JOB_NOT_FOUND, "Job not found")),
+		};
+		let nonce = params.get(2).and_then(parse_u256).ok_or((ERROR_OTHER, "Nonce expected"))?;
+		if (nonce >> 192) != U256::from(self.extranonce) {
+			return Err((ERROR_OTHER, "Nonce does not start with the extranonce"))
+		}
+		if self.nonces.contains(&nonce) {
+			return Err((ERROR_DUPLICATE_SHARE, "Duplicate share"))
+		}
+		if self.nonces.len() >= MAX_SHARES_PER_JOB {
+			return Err((ERROR_OTHER, "Too many shares for this job, raise the share difficulty"))
+		}
+		self.nonces.insert(nonce);
+
+		let solution = Compute { difficulty: job.difficulty, pre_hash: job.pre_hash, nonce }
+			.compute(self.hasher);
+		let share_difficulty = self.effective_difficulty(&job);
+		if !hash_meets_difficulty(&solution.work, share_difficulty) {
+			return Err((ERROR_LOW_DIFFICULTY, "Low difficulty share"))
+		}
+
+		let total = self.shares.record(&name, share_difficulty);
+		trace!(target: LOG_TARGET, "Share from {}, {} in total", name, total.shares);
+
+		if hash_meets_difficulty(&solution.work, job.difficulty) {
+			let worker = self.worker.clone();
+			// Signing and importing a block is blocking work
+			let accepted = tokio::task::spawn_blocking(move || worker.submit(solution))
+				.await
+				.unwrap_or(false);
+			if accepted {
+				info!(target: LOG_TARGET, "Block found by {}", name);
+			} else {
+				debug!(target: LOG_TARGET, "Block from {} was not accepted", name);
+			}
+		}
+
+		Ok(json!(true))
+	}
+
+	// Share difficulty of the connection for the given job, never above the block difficulty
+	fn effective_difficulty(&self, job: &MiningJob) -> U256 {
+		self.share_difficulty.min(job.difficulty)
+	}
+
+	// Switch to the given job, shares of the previous one are stale from now on
+	fn set_job(&mut self, job: Option<MiningJob>) {
+		self.nonces.clear();
+		self.job = job;
+	}
+
+	async fn notify(
+		&mut self,
+		writer: &mut OwnedWriteHalf,
+		job: Option<MiningJob>,
+	) -> std::io::Result<()> {
+		self.set_job(job);
+
+		let job = match &self.job {
+			Some(job) => job.clone(),
+			None => return Ok(()),
+		};
+
+		send(
+			writer,
+			json!({
+				"id": null,
+				"method": "mining.set_difficulty",
+				"params": [self.effective_difficulty(&job)],
+			}),
+		)
+		.await?;
+		send(
+			writer,
+			json!({
+				"id": null,
+				"method": "mining.notify",
+				"params": [format!("{:x}", job.id), job.pre_hash, job.difficulty, true],
+			}),
+		)
+		.await
+	}
+}
+
+async fn send(writer: &mut OwnedWriteHalf, message: Value) -> std::io::Result<()> {
+	let mut line = message.to_string();
+	line.push('\n');
+	writer.write_all(line.as_bytes()).await
+}
+
+// Accept both `0x` prefixed hex strings and plain numbers
+fn parse_u256(value: &Value) -> Option<U256> {
+	match value {
+		Value::String(s) => match s.strip_prefix("0x") {
+			Some(hex) => U256::from_str_radix(hex, 16).ok(),
+			None => U256::from_dec_str(s).ok(),
+		},
+		Value::Number(n) => n.as_u64().map(U256::from),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crain_pow::{hash::Sha3Hasher, Solution};
+	use sp_core::H256;
+
+	// Worker that accepts every block and keeps the solutions
+	#[derive(Clone, Default)]
+	struct MockWorker {
+		submitted: Arc<Mutex<Vec<Solution>>>,
+	}
+
+	impl WorkSource for MockWorker {
+		fn job_id(&self) -> usize {
+			0
+		}
+
+		fn work(&self) -> Option<MiningJob> {
+			None
+		}
+
+		fn submit(&self, solution: Solution) -> bool {
+			self.submitted.lock().unwrap().push(solution);
+			true
+		}
+	}
+
+	const EXTRANONCE: u64 = 42;
+
+	fn job(id: usize, difficulty: U256) -> MiningJob {
+		MiningJob {
+			id,
+			parent: H256::repeat_byte(id as u8 + 1),
+			pre_hash: H256::repeat_byte(id as u8),
+			difficulty,
+		}
+	}
+
+	fn connection(share_difficulty: U256) -> Connection<MockWorker> {
+		Connection::new(
+			MockWorker::default(),
+			&Sha3Hasher,
+			ShareBook::default(),
+			EXTRANONCE,
+			share_difficulty,
+		)
+	}
+
+	// Subscribed connection of worker `alice`, working on a job no hash solves
+	async fn ready(share_difficulty: U256) -> Connection<MockWorker> {
+		let mut connection = connection(share_difficulty);
+		connection.handle("mining.subscribe", &[]).await.unwrap();
+		connection.handle("mining.authorize", &[json!("alice")]).await.unwrap();
+		connection.set_job(Some(job(1, U256::MAX)));
+		connection
+	}
+
+	// Hex encoded nonce of the connection with the given lower bits
+	fn nonce(low: u64) -> Value {
+		json!(format!("0x{:x}", (U256::from(EXTRANONCE) << 192) + U256::from(low)))
+	}
+
+	async fn submit(connection: &mut Connection<MockWorker>, job_id: &str, nonce: Value) -> Reply {
+		connection.handle("mining.submit", &[json!("alice"), json!(job_id), nonce]).await
+	}
+
+	fn code(reply: Reply) -> i64 {
+		reply.unwrap_err().0
+	}
+
+	#[tokio::test]
+	async fn subscribe_returns_the_extranonce() {
+		let mut connection = connection(U256::one());
+		let result = connection.handle("mining.subscribe", &[]).await.unwrap();
+
+		assert!(connection.subscribed);
+		assert_eq!(result[1], json!("000000000000002a"));
+		assert_eq!(result[2], json!(24));
+	}
+
+	#[tokio::test]
+	async fn authorize_needs_a_worker_name() {
+		let mut connection = connection(U256::one());
+
+		assert_eq!(code(connection.handle("mining.authorize", &[]).await), ERROR_OTHER);
+		assert_eq!(code(connection.handle("mining.authorize", &[json!(7)]).await), ERROR_OTHER);
+		assert_eq!(connection.handle("mining.authorize", &[json!("alice")]).await, Ok(json!(true)));
+		assert_eq!(connection.authorized.as_deref(), Some("alice"));
+		assert_eq!(connection.shares.snapshot()["alice"], WorkerShares::default());
+	}
+
+	#[tokio::test]
+	async fn authorize_refuses_workers_beyond_the_limit() {
+		let mut connection = connection(U256::one());
+		for index in 0..MAX_WORKERS {
+			assert!(connection.shares.register(&format!("worker{}", index)));
+		}
+
+		let reply = connection.handle("mining.authorize", &[json!("alice")]).await;
+		assert_eq!(code(reply), ERROR_OTHER);
+		assert!(connection.handle("mining.authorize", &[json!("worker0")]).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn unknown_methods_are_rejected() {
+		let mut connection = connection(U256::one());
+		assert_eq!(code(connection.handle("mining.extranonce.subscribe", &[]).await), ERROR_OTHER);
+	}
+
+	#[tokio::test]
+	async fn suggest_difficulty_sets_the_share_difficulty() {
+		let mut connection = connection(U256::one());
+
+		connection.handle("mining.suggest_difficulty", &[json!(500)]).await.unwrap();
+		assert_eq!(connection.share_difficulty, U256::from(500));
+
+		connection.handle("mining.suggest_difficulty", &[json!("0x0")]).await.unwrap();
+		assert_eq!(connection.share_difficulty, U256::one());
+
+		let reply = connection.handle("mining.suggest_difficulty", &[json!("much")]).await;
+		assert_eq!(code(reply), ERROR_OTHER);
+	}
+
+	#[tokio::test]
+	async fn submit_needs_a_subscription_and_the_authorized_worker() {
+		let mut connection = connection(U256::one());
+		connection.set_job(Some(job(1, U256::MAX)));
+		assert_eq!(code(submit(&mut connection, "1", nonce(0)).await), ERROR_NOT_SUBSCRIBED);
+
+		connection.handle("mining.subscribe", &[]).await.unwrap();
+		assert_eq!(code(submit(&mut connection, "1", nonce(0)).await), ERROR_UNAUTHORIZED);
+
+		connection.handle("mining.authorize", &[json!("bob")]).await.unwrap();
+		assert_eq!(code(submit(&mut connection, "1", nonce(0)).await), ERROR_UNAUTHORIZED);
+	}
+
+	#[tokio::test]
+	async fn submit_parses_the_nonce() {
+		let mut connection = ready(U256::one()).await;
+
+		let decimal = (U256::from(EXTRANONCE) << 192).to_string();
+		assert_eq!(submit(&mut connection, "1", json!(decimal)).await, Ok(json!(true)));
+		assert_eq!(submit(&mut connection, "1", nonce(1)).await, Ok(json!(true)));
+
+		let reply = connection.handle("mining.submit", &[json!("alice"), json!("1")]).await;
+		assert_eq!(code(reply), ERROR_OTHER);
+		assert_eq!(code(submit(&mut connection, "1", json!("0xzz")).await), ERROR_OTHER);
+		// Outside of the extranonce of the connection
+		assert_eq!(code(submit(&mut connection, "1", json!("0x1")).await), ERROR_OTHER);
+	}
+
+	#[tokio::test]
+	async fn duplicate_shares_are_rejected() {
+		let mut connection = ready(U256::one()).await;
+
+		assert_eq!(submit(&mut connection, "1", nonce(7)).await, Ok(json!(true)));
+		assert_eq!(code(submit(&mut connection, "1", nonce(7)).await), ERROR_DUPLICATE_SHARE);
+		assert_eq!(connection.shares.snapshot()["alice"].shares, 1);
+	}
+
+	#[tokio::test]
+	async fn shares_of_stale_jobs_are_rejected() {
+		let mut connection = ready(U256::one()).await;
+		connection.set_job(Some(job(2, U256::MAX)));
+
+		assert_eq!(code(submit(&mut connection, "1", nonce(0)).await), ERROR_JOB_NOT_FOUND);
+		assert_eq!(submit(&mut connection, "2", nonce(0)).await, Ok(json!(true)));
+
+		connection.set_job(None);
+		assert_eq!(code(submit(&mut connection, "2", nonce(1)).await), ERROR_JOB_NOT_FOUND);
+	}
+
+	#[tokio::test]
+	async fn low_difficulty_shares_are_rejected() {
+		// No hash meets the highest difficulty
+		let mut connection = ready(U256::MAX).await;
+
+		assert_eq!(code(submit(&mut connection, "1", nonce(0)).await), ERROR_LOW_DIFFICULTY);
+		assert_eq!(connection.shares.snapshot()["alice"], WorkerShares::default());
+		assert!(connection.worker.submitted.lock().unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn accepted_shares_are_booked_to_the_worker() {
+		let mut connection = ready(U256::from(3)).await;
+
+		let mut accepted = 0;
+		for low in 0..100 {
+			if submit(&mut connection, "1", nonce(low)).await.is_ok() {
+				accepted += 1;
+			}
+		}
+
+		assert!(accepted > 0);
+		let shares = connection.shares.snapshot()["alice"];
+		assert_eq!(shares.shares, accepted);
+		assert_eq!(shares.difficulty, U256::from(3 * accepted));
+	}
+
+	#[tokio::test]
+	async fn shares_meeting_the_block_difficulty_are_submitted() {
+		let mut connection = ready(U256::one()).await;
+		// Every hash meets difficulty 1
+		connection.set_job(Some(job(2, U256::one())));
+
+		assert_eq!(submit(&mut connection, "2", nonce(0)).await, Ok(json!(true)));
+		let submitted = connection.worker.submitted.lock().unwrap().clone();
+		assert_eq!(submitted.len(), 1);
+		assert_eq!(submitted[0].nonce, U256::from(EXTRANONCE) << 192);
+	}
+
+	#[tokio::test]
+	async fn shares_per_job_are_limited() {
+		let mut connection = ready(U256::one()).await;
+		let limit = MAX_SHARES_PER_JOB as u64;
+		for low in 0..limit {
+			assert_eq!(submit(&mut connection, "1", nonce(low)).await, Ok(json!(true)));
+		}
+		assert_eq!(code(submit(&mut connection, "1", nonce(limit)).await), ERROR_OTHER);
+
+		// A new job starts over
+		connection.set_job(Some(job(2, U256::MAX)));
+		assert_eq!(submit(&mut connection, "2", nonce(limit)).await, Ok(json!(true)));
+	}
+
+	#[test]
+	fn parse_u256_accepts_hex_decimal_and_numbers() {
+		assert_eq!(parse_u256(&json!("0xff")), Some(U256::from(255)));
+		assert_eq!(parse_u256(&json!("255")), Some(U256::from(255)));
+		assert_eq!(parse_u256(&json!(255)), Some(U256::from(255)));
+		assert_eq!(parse_u256(&json!("ff")), None);
+		assert_eq!(parse_u256(&json!(-1)), None);
+		assert_eq!(parse_u256(&json!(null)), None);
+	}
+}
@@ -0,0 +1,323 @@
+//! Difficulty adjustment algorithms.
+//!
+//! Every algorithm computes the difficulty of the next block from a [`WindowSummary`] of the
+//! last blocks, so the pallet can keep the summary up to date without knowing the algorithm.
+
+use crate::{clamp, damp};
+use crain_primitives::Difficulty;
+use parity_scale_codec::{Decode, Encode, MaxEncodedLen};
+use scale_info::TypeInfo;
+use sp_std::cmp::{max, min};
+
+/// Parameters shared by the difficulty algorithms.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RetargetParams {
+	/// Target block time in milliseconds.
+	pub target_block_time: u128,
+	/// Dampening factor of [`DampClamp`].
+	pub damp_factor: u128,
+	/// Clamping factor of [`DampClamp`].
+	pub clamp_factor: u128,
+	/// Half-life of [`Exponential`] in blocks.
+	pub half_life: u32,
+	/// Lowest difficulty.
+	pub min_difficulty: u128,
+	/// Highest difficulty.
+	pub max_difficulty: u128,
+}
+
+impl RetargetParams {
+	/// Bound the difficulty by the lowest and the highest one.
+	pub fn bound(&self, difficulty: Difficulty) -> Difficulty {
+		min(
+			Difficulty::from(self.max_difficulty),
+			max(Difficulty::from(self.min_difficulty), difficulty),
+		)
+	}
+}
+
+/// Running sums over the blocks of the retarget window.
+#[derive(Encode, Decode, TypeInfo, MaxEncodedLen, Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct WindowSummary {
+	/// Number of blocks in the window.
+	pub blocks: u32,
+	/// Difficulty of the earliest block.
+	pub first_difficulty: Difficulty,
+	/// Sum of the difficulties of the blocks.
+	pub difficulty_sum: Difficulty,
+	/// Sum of the solve times, the times between two consecutive blocks, in milliseconds.
+	pub solve_time_sum: u128,
+	/// Sum of the solve times weighted by their position in the window, from 1 for the
+	/// earliest one.
+	pub weighted_solve_time_sum: u128,
+}
+
+impl WindowSummary {
+	/// Add the next block of the window, mined `solve_time` after the previous one.
+	/// The solve time of the earliest block is not used.
+	pub fn push(&mut self, difficulty: Difficulty, solve_time: u128) {
+		if self.blocks == 0 {
+			self.first_difficulty = difficulty;
+		} else {
+			self.solve_time_sum = self.solve_time_sum.saturating_add(solve_time);
+			self.weighted_solve_time_sum = self
+				.weighted_solve_time_sum
+				.saturating_add(solve_time.saturating_mul(self.blocks as u128));
+		}
+		self.blocks += 1;
+		self.difficulty_sum = self.difficulty_sum.saturating_add(difficulty);
+	}
+
+	/// Number of solve times in the window.
+	pub fn solve_times(&self) -> u128 {
+		self.blocks.saturating_sub(1) as u128
+	}
+}
+
+/// Algorithm computing the difficulty of the next block.
+pub trait DifficultyAlgorithm {
+	/// Difficulty of the block after the window.
+	fn next_difficulty(window: &WindowSummary, params: &RetargetParams) -> Difficulty;
+}
+
+/// Grin/Kulupu algorithm: the time the window took is damped and clamped toward the target
+/// before it scales the average difficulty.
+pub struct DampClamp;
+
+impl DifficultyAlgorithm for DampClamp {
+	fn next_difficulty(window: &WindowSummary, params: &RetargetParams) -> Difficulty {
+		let block_time = params.target_block_time;
+		let block_time_window = window.blocks as u128 * block_time;
+
+		let ts_delta = max(window.solve_time_sum, 1);
+		let diff_sum = max(window.difficulty_sum, Difficulty::from(params.min_difficulty));
+
+		// adjust time delta toward goal subject to dampening and clamping
+		let adj_ts = clamp(
+			damp(ts_delta, block_time_window, params.damp_factor),
+			block_time_window,
+			params.clamp_factor,
+		);
+
+		// minimum difficulty avoids getting stuck due to dampening
+		params.bound(diff_sum * Difficulty::from(block_time) / Difficulty::from(max(adj_ts, 1)))
+	}
+}
+
+/// Linearly weighted moving average: recent solve times weigh more, so the difficulty follows
+/// hashrate changes faster than with a plain average.
+pub struct Lwma;
+
+impl DifficultyAlgorithm for Lwma {
+	fn next_difficulty(window: &WindowSummary, params: &RetargetParams) -> Difficulty {
+		if window.blocks == 0 {
+			return params.bound(Difficulty::zero())
+		}
+
+		let solve_times = window.solve_times();
+		// Weighted sum of the solve times if every block is on target
+		let target = (solve_times * (solve_times + 1) / 2).saturating_mul(params.target_block_time);
+		// A burst of blocks with equal timestamps does not raise the difficulty more than 10 times
+		let weighted = max(window.weighted_solve_time_sum, max(target / 10, 1));
+
+		params.bound(
+			window.difficulty_sum.saturating_mul(Difficulty::from(target)) /
+				(Difficulty::from(window.blocks) * Difficulty::from(weighted)),
+		)
+	}
+}
+
+/// Exponential adjustment of the difficulty of the earliest block of the window: the difficulty
+/// doubles or halves for every half-life of target block times the window is ahead of or behind
+/// the target. Only the window is looked at, so the schedule moves with it.
+pub struct Exponential;
+
+impl DifficultyAlgorithm for Exponential {
+	fn next_difficulty(window: &WindowSummary, params: &RetargetParams) -> Difficulty {
+		let block_time = params.target_block_time.min(i128::MAX as u128) as i128;
+		let tau = max(block_time.saturating_mul(params.half_life as i128), 1);
+		let expected = block_time.saturating_mul(window.solve_times() as i128);
+		let elapsed = window.solve_time_sum.min(i128::MAX as u128) as i128;
+
+		// Half-lives ahead of the schedule in fixed point with 16 fractional bits
+		let exponent = expected.saturating_sub(elapsed).saturating_mul(65536).div_euclid(tau);
+		let shifts = exponent >> 16;
+		let frac = (exponent & 0xffff) as u128;
+
+		// 2^frac in fixed point by a cubic approximation
+		let factor = 65536 +
+			((195_766_423_245_049 * frac +
+				971_821_376 * frac * frac +
+				5127 * frac * frac * frac +
+				(1 << 47)) >> 48);
+
+		let difficulty = window.first_difficulty.saturating_mul(Difficulty::from(factor));
+		let shift = shifts - 16;
+		let difficulty = if shift >= 0 {
+			if shift >= difficulty.leading_zeros() as i128 {
+				Difficulty::MAX
+			} else {
+				difficulty << shift as usize
+			}
+		} else if -shift >= 256 {
+			Difficulty::zero()
+		} else {
+			difficulty >> (-shift) as usize
+		};
+
+		params.bound(difficulty)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PARAMS: RetargetParams = RetargetParams {
+		target_block_time: 1000,
+		damp_factor: 3,
+		clamp_factor: 2,
+		half_life: 10,
+		min_difficulty: 3,
+		max_difficulty: u128::MAX,
+	};
+
+	/// Blocks of a window and the next difficulty every algorithm gives for them.
+	struct Vector {
+		name: &'static str,
+		difficulties: [u128; 10],
+		solve_times: [u128; 9],
+		damp_clamp: u128,
+		lwma: u128,
+		exponential: u128,
+	}
+
+	const VECTORS: &[Vector] = &[
+		Vector {
+			name: "on target",
+			difficulties: [1000; 10],
+			solve_times: [1000; 9],
+			damp_clamp: 1034,
+			lwma: 1000,
+			exponential: 1000,
+		},
+		Vector {
+			name: "fast blocks",
+			difficulties: [1000; 10],
+			solve_times: [500; 9],
+			damp_clamp: 1224,
+			lwma: 2000,
+			exponential: 1365,
+		},
+		Vector {
+			name: "slow blocks",
+			difficulties: [1000; 10],
+			solve_times: [2000; 9],
+			damp_clamp: 789,
+			lwma: 500,
+			exponential: 535,
+		},
+		Vector {
+			name: "stalled block",
+			difficulties: [1000; 10],
+			solve_times: [1000, 1000, 1000, 1000, 1000, 1000, 1000, 1000, 60_000],
+			damp_clamp: 500,
+			lwma: 78,
+			exponential: 16,
+		},
+		Vector {
+			name: "equal timestamps",
+			difficulties: [1000; 10],
+			solve_times: [0; 9],
+			damp_clamp: 1499,
+			lwma: 10_000,
+			exponential: 1866,
+		},
+		Vector {
+			name: "rising difficulty",
+			difficulties: [1000, 1100, 1200, 1300, 1400, 1500, 1600, 1700, 1800, 1900],
+			solve_times: [1000; 9],
+			damp_clamp: 1500,
+			lwma: 1450,
+			exponential: 1000,
+		},
+		Vector {
+			name: "zero difficulty",
+			difficulties: [0; 10],
+			solve_times: [1000; 9],
+			damp_clamp: 3,
+			lwma: 3,
+			exponential: 3,
+		},
+	];
+
+	fn summary(vector: &Vector) -> WindowSummary {
+		let mut summary = WindowSummary::default();
+		summary.push(vector.difficulties[0].into(), 0);
+		for (difficulty, solve_time) in vector.difficulties[1..].iter().zip(vector.solve_times) {
+			summary.push((*difficulty).into(), solve_time);
+		}
+		summary
+	}
+
+	fn check<A: DifficultyAlgorithm>(expected: impl Fn(&Vector) -> u128) {
+		for vector in VECTORS {
+			assert_eq!(
+				A::next_difficulty(&summary(vector), &PARAMS),
+				Difficulty::from(expected(vector)),
+				"{}",
+				vector.name,
+			);
+		}
+	}
+
+	#[test]
+	fn damp_clamp_matches_vectors() {
+		check::<DampClamp>(|vector| vector.damp_clamp);
+	}
+
+	#[test]
+	fn lwma_matches_vectors() {
+		check::<Lwma>(|vector| vector.lwma);
+	}
+
+	#[test]
+	fn exponential_matches_vectors() {
+		check::<Exponential>(|vector| vector.exponential);
+	}
+
+	#[test]
+	fn summary_sums_the_window() {
+		let summary = summary(&VECTORS[5]);
+
+		assert_eq!(summary.blocks, 10);
+		assert_eq!(summary.first_difficulty, Difficulty::from(1000));
+		assert_eq!(summary.difficulty_sum, Difficulty::from(14_500));
+		assert_eq!(summary.solve_time_sum, 9000);
+		assert_eq!(summary.weighted_solve_time_sum, 45_000);
+	}
+
+	#[test]
+	fn difficulty_is_bounded() {
+		let params = RetargetParams { min_difficulty: 900, max_difficulty: 1100, ..PARAMS };
+		let fast = summary(&VECTORS[1]);
+		let slow = summary(&VECTORS[2]);
+
+		assert_eq!(DampClamp::next_difficulty(&fast, &params), Difficulty::from(1100));
+		assert_eq!(Lwma::next_difficulty(&slow, &params), Difficulty::from(900));
+		assert_eq!(Exponential::next_difficulty(&slow, &params), Difficulty::from(900));
+	}
+
+	#[test]
+	fn exponential_saturates_on_huge_exponents() {
+		let mut window = summary(&VECTORS[0]);
+		window.first_difficulty = Difficulty::MAX;
+		assert_eq!(Exponential::next_difficulty(&window, &PARAMS), Difficulty::from(u128::MAX));
+
+		window.solve_time_sum = 0;
+		assert_eq!(Exponential::next_difficulty(&window, &PARAMS), Difficulty::from(u128::MAX));
+
+		window.solve_time_sum = u128::MAX;
+		assert_eq!(Exponential::next_difficulty(&window, &PARAMS), Difficulty::from(3));
+	}
+}
@@ -0,0 +1,54 @@
+//! Benchmarks of the difficulty pallet.
+
+use super::*;
+use frame_benchmarking::{benchmarks, impl_benchmark_test_suite};
+use frame_system::RawOrigin;
+
+// Fill the window with `blocks` blocks mined on target
+fn fill_window<T: Config>(blocks: u32) {
+	let block_time = T::TargetBlockTime::get();
+	let mut now = T::Moment::default();
+	for _ in 0..blocks {
+		now += block_time;
+		Pallet::<T>::on_timestamp_set(now);
+	}
+}
+
+benchmarks! {
+	// The cost does not depend on the number of blocks in the window. The worst case is a full
+	// window, where the earliest block is read to be evicted and its slot is written, and a
+	// block with the timestamp of its parent, which changes the difficulty and deposits
+	// `DifficultyAdjusted`.
+	on_timestamp_set {
+		let size = T::WindowSize::get();
+		fill_window::<T>(size);
+		let now = T::TargetBlockTime::get() * size.into();
+		let old = Pallet::<T>::difficulty();
+	}: {
+		Pallet::<T>::on_timestamp_set(now);
+	}
+	verify {
+		assert_eq!(Pallet::<T>::window_state().len, size);
+		assert_ne!(Pallet::<T>::difficulty(), old);
+	}
+
+	set_difficulty {
+		let b in 1 .. T::WindowSize::get();
+		fill_window::<T>(b);
+		let difficulty = Difficulty::from(T::MinDifficulty::get());
+	}: _(RawOrigin::Root, difficulty)
+	verify {
+		assert_eq!(Pallet::<T>::difficulty(), difficulty);
+		assert_eq!(Pallet::<T>::window_state().len, 0);
+	}
+
+	reset_window {
+		let b in 1 .. T::WindowSize::get();
+		fill_window::<T>(b);
+	}: _(RawOrigin::Root)
+	verify {
+		assert_eq!(Pallet::<T>::window_state().len, 0);
+	}
+}
+
+impl_benchmark_test_suite!(Pallet, crate::mock::new_test_ext(), crate::mock::Test);
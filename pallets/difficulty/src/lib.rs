@@ -1,26 +1,43 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 //! Difficulty adjustment module.
+//!
+//! Retargets the difficulty every time the timestamp of a block is set, from the difficulties
+//! and timestamps of the last `WindowSize` blocks. The algorithm and its parameters are picked
+//! by the runtime, the algorithms are in the [`algorithm`] module.
+//!
+//! The pallet also keeps the PoW algorithm of the chain, set at genesis, which the node reads
+//! through `PowAlgorithmApi`.
 
-use parity_scale_codec::{Decode, Encode};
+pub use algorithm::{
+	DampClamp, DifficultyAlgorithm, Exponential, Lwma, RetargetParams, WindowSummary,
+};
+pub use pallet::*;
+pub use window::WindowState;
+
+pub mod algorithm;
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+pub mod migrations;
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+pub mod weights;
+pub mod window;
+
+pub use crain_primitives::DifficultyAndTimestamp;
+use crain_primitives::{hash::HashAlgorithm, Difficulty};
 use frame_support::{
-	decl_module, decl_storage,
 	traits::{Get, OnTimestampSet},
+	weights::DispatchClass,
 };
-use crain_primitives::{
-	Difficulty, CLAMP_FACTOR, DIFFICULTY_ADJUST_WINDOW, DIFFICULTY_DAMP_FACTOR, MAX_DIFFICULTY,
-	MIN_DIFFICULTY,
-};
-use scale_info::TypeInfo;
-use sp_core::U256;
 use sp_runtime::traits::UniqueSaturatedInto;
-use sp_std::cmp::{max, min};
-
-#[derive(Encode, Decode, TypeInfo, Clone, Copy, Eq, PartialEq, Debug)]
-pub struct DifficultyAndTimestamp<M> {
-	pub difficulty: Difficulty,
-	pub timestamp: M,
-}
+use sp_std::{
+	cmp::{max, min},
+	vec::Vec,
+};
+pub use weights::WeightInfo;
 
 /// Move value linearly toward a goal
 pub fn damp(actual: u128, goal: u128, damp_factor: u128) -> u128 {
@@ -32,94 +49,263 @@ pub fn clamp(actual: u128, goal: u128, clamp_factor: u128) -> u128 {
 	max(goal / clamp_factor, min(actual, goal * clamp_factor))
 }
 
-pub trait Config: pallet_timestamp::Config {
-	/// Target block time in millseconds.
-	type TargetBlockTime: Get<Self::Moment>;
-}
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
 
-decl_storage! {
-	trait Store for Module<T: Config> as Difficulty {
-		/// Past difficulties and timestamps, from earliest to latest.
-		PastDifficultiesAndTimestamps: [Option<DifficultyAndTimestamp<T::Moment>>; 60] = [None; DIFFICULTY_ADJUST_WINDOW as usize];
-		/// Current difficulty.
-		pub CurrentDifficulty get(fn difficulty) build(|config: &GenesisConfig| {
-			config.initial_difficulty
-		}): Difficulty;
-		/// Initial difficulty.
-		pub InitialDifficulty config(initial_difficulty): Difficulty;
-	}
-}
+	/// The current storage version.
+	pub const STORAGE_VERSION: StorageVersion = StorageVersion::new(3);
 
-decl_module! {
-	pub struct Module<T: Config> for enum Call where origin: T::Origin {
+	#[pallet::config]
+	pub trait Config: frame_system::Config + pallet_timestamp::Config {
+		type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
 		/// Target block time in milliseconds.
-		const TargetBlockTime: T::Moment = T::TargetBlockTime::get();
+		#[pallet::constant]
+		type TargetBlockTime: Get<Self::Moment>;
+		/// Algorithm computing the difficulty of the next block.
+		type DifficultyAlgorithm: DifficultyAlgorithm;
+		/// Number of past blocks the difficulty is computed from. Changing it needs a new window.
+		#[pallet::constant]
+		type WindowSize: Get<u32>;
+		/// Dampening factor of the damp and clamp algorithm.
+		#[pallet::constant]
+		type DampFactor: Get<u128>;
+		/// Clamping factor of the damp and clamp algorithm.
+		#[pallet::constant]
+		type ClampFactor: Get<u128>;
+		/// Half-life of the exponential algorithm in blocks.
+		#[pallet::constant]
+		type HalfLife: Get<u32>;
+		/// Lowest difficulty.
+		#[pallet::constant]
+		type MinDifficulty: Get<u128>;
+		/// Highest difficulty.
+		#[pallet::constant]
+		type MaxDifficulty: Get<u128>;
+		/// Weights of the calls and the retarget.
+		type WeightInfo: WeightInfo;
 	}
-}
 
-impl<T: Config> OnTimestampSet<T::Moment> for Module<T> {
-	fn on_timestamp_set(now: T::Moment) {
-		let block_time =
-			UniqueSaturatedInto::<u128>::unique_saturated_into(T::TargetBlockTime::get());
-		let block_time_window = DIFFICULTY_ADJUST_WINDOW as u128 * block_time;
+	#[pallet::pallet]
+	#[pallet::storage_version(STORAGE_VERSION)]
+	pub struct Pallet<T>(_);
+
+	/// Past difficulties and timestamps in the slots of a ring of `WindowSize` slots.
+	#[pallet::storage]
+	pub type WindowBlocks<T: Config> =
+		StorageMap<_, Twox64Concat, u32, DifficultyAndTimestamp<T::Moment>>;
+
+	/// Slots of the blocks of the window and running sums over them.
+	#[pallet::storage]
+	#[pallet::getter(fn window_state)]
+	pub type Window<T: Config> = StorageValue<_, WindowState, ValueQuery>;
 
-		let mut data = PastDifficultiesAndTimestamps::<T>::get();
+	/// Current difficulty.
+	#[pallet::storage]
+	#[pallet::getter(fn difficulty)]
+	pub type CurrentDifficulty<T: Config> = StorageValue<_, Difficulty, ValueQuery>;
 
-		for i in 1..data.len() {
-			data[i - 1] = data[i];
+	/// Difficulty of the blocks before the window: the genesis one, or the current one when
+	/// the window was last reset.
+	#[pallet::storage]
+	#[pallet::getter(fn initial_difficulty)]
+	pub type InitialDifficulty<T: Config> = StorageValue<_, Difficulty, ValueQuery>;
+
+	/// PoW algorithm of the chain, set at genesis. The node reads it through `PowAlgorithmApi`.
+	#[pallet::storage]
+	#[pallet::getter(fn pow_algorithm)]
+	pub type PowAlgorithm<T: Config> = StorageValue<_, HashAlgorithm, ValueQuery>;
+
+	#[pallet::genesis_config]
+	pub struct GenesisConfig {
+		pub initial_difficulty: Difficulty,
+		pub pow_algorithm: HashAlgorithm,
+	}
+
+	#[cfg(feature = "std")]
+	impl Default for GenesisConfig {
+		fn default() -> Self {
+			Self { initial_difficulty: Default::default(), pow_algorithm: Default::default() }
 		}
+	}
 
-		data[data.len() - 1] = Some(DifficultyAndTimestamp {
-			timestamp: now,
-			difficulty: Self::difficulty(),
-		});
+	#[pallet::genesis_build]
+	impl<T: Config> GenesisBuild<T> for GenesisConfig {
+		fn build(&self) {
+			<CurrentDifficulty<T>>::put(self.initial_difficulty);
+			<InitialDifficulty<T>>::put(self.initial_difficulty);
+			<PowAlgorithm<T>>::put(self.pow_algorithm);
+		}
+	}
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// The difficulty of the next block changed.
+		DifficultyAdjusted { old: Difficulty, new: Difficulty },
+		/// The difficulty was set by root.
+		DifficultySet { old: Difficulty, new: Difficulty },
+		/// The retarget window was emptied. It held `blocks` blocks.
+		WindowReset { blocks: u32 },
+	}
 
-		let mut ts_delta = 0;
-		for i in 1..(DIFFICULTY_ADJUST_WINDOW as usize) {
-			let prev: Option<u128> = data[i - 1].map(|d| d.timestamp.unique_saturated_into());
-			let cur: Option<u128> = data[i].map(|d| d.timestamp.unique_saturated_into());
+	#[pallet::error]
+	pub enum Error<T> {
+		/// The difficulty is lower than `MinDifficulty` or higher than `MaxDifficulty`.
+		DifficultyOutOfBounds,
+	}
 
-			let delta = match (prev, cur) {
-				(Some(prev), Some(cur)) => cur.saturating_sub(prev),
-				_ => block_time.into(),
-			};
-			ts_delta += delta;
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn integrity_test() {
+			assert!(T::DampFactor::get() >= 1, "`DampFactor` must be at least 1");
+			assert!(T::ClampFactor::get() >= 1, "`ClampFactor` must be at least 1");
+			assert!(
+				T::MinDifficulty::get() <= T::MaxDifficulty::get(),
+				"`MinDifficulty` must not be higher than `MaxDifficulty`"
+			);
+			assert!(T::WindowSize::get() > 0, "`WindowSize` must not be 0");
 		}
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {
+		/// Set the difficulty of the next block, for instance when the hashrate collapsed.
+		/// The window is emptied as well, so that the retarget starts from this difficulty
+		/// instead of going back to the one of the blocks in the window.
+		#[pallet::weight(T::WeightInfo::set_difficulty(T::WindowSize::get()))]
+		pub fn set_difficulty(
+			origin: OriginFor<T>,
+			difficulty: Difficulty,
+		) -> DispatchResultWithPostInfo {
+			ensure_root(origin)?;
+			ensure!(
+				difficulty >= Difficulty::from(T::MinDifficulty::get()) &&
+					difficulty <= Difficulty::from(T::MaxDifficulty::get()),
+				Error::<T>::DifficultyOutOfBounds
+			);
+
+			let old = Self::difficulty();
+			let blocks = Self::clear_window(difficulty);
+			<CurrentDifficulty<T>>::put(difficulty);
 
-		if ts_delta == 0 {
-			ts_delta = 1;
+			Self::deposit_event(Event::DifficultySet { old, new: difficulty });
+			Ok(Some(T::WeightInfo::set_difficulty(blocks)).into())
 		}
 
-		let mut diff_sum = U256::zero();
-		for i in 0..(DIFFICULTY_ADJUST_WINDOW as usize) {
-			let diff = match data[i].map(|d| d.difficulty) {
-				Some(diff) => diff,
-				None => InitialDifficulty::get(),
-			};
-			diff_sum += diff;
+		/// Forget the blocks of the retarget window. Until it is full again, the missing blocks
+		/// count as mined on target at the current difficulty.
+		#[pallet::weight(T::WeightInfo::reset_window(T::WindowSize::get()))]
+		pub fn reset_window(origin: OriginFor<T>) -> DispatchResultWithPostInfo {
+			ensure_root(origin)?;
+
+			let blocks = Self::clear_window(Self::difficulty());
+
+			Self::deposit_event(Event::WindowReset { blocks });
+			Ok(Some(T::WeightInfo::reset_window(blocks)).into())
 		}
+	}
+}
 
-		if diff_sum < U256::from(MIN_DIFFICULTY) {
-			diff_sum = U256::from(MIN_DIFFICULTY);
+impl<T: Config> Pallet<T> {
+	/// Parameters the difficulty algorithm runs with.
+	pub fn retarget_params() -> RetargetParams {
+		RetargetParams {
+			target_block_time: T::TargetBlockTime::get().unique_saturated_into(),
+			damp_factor: T::DampFactor::get(),
+			clamp_factor: T::ClampFactor::get(),
+			half_life: T::HalfLife::get(),
+			min_difficulty: T::MinDifficulty::get(),
+			max_difficulty: T::MaxDifficulty::get(),
 		}
+	}
 
-		// adjust time delta toward goal subject to dampening and clamping
-		let adj_ts = clamp(
-			damp(ts_delta, block_time_window, DIFFICULTY_DAMP_FACTOR),
-			block_time_window,
-			CLAMP_FACTOR,
-		);
+	/// Blocks of the retarget window, from earliest to latest.
+	pub fn window() -> Vec<DifficultyAndTimestamp<T::Moment>> {
+		let size = T::WindowSize::get();
+		let state = Self::window_state();
+		(0..state.len).filter_map(|index| <WindowBlocks<T>>::get(state.slot(index, size))).collect()
+	}
 
-		// minimum difficulty avoids getting stuck due to dampening
-		let difficulty = min(
-			U256::from(MAX_DIFFICULTY),
-			max(
-				U256::from(MIN_DIFFICULTY),
-				diff_sum * U256::from(block_time) / U256::from(adj_ts),
-			),
+	/// Difficulty after the next block, if it is mined on target.
+	pub fn predicted_difficulty() -> Difficulty {
+		let params = Self::retarget_params();
+		let state = Self::window_state();
+		let now = state.latest_timestamp.saturating_add(params.target_block_time);
+		let (state, _) = Self::advance(state, now, Self::difficulty());
+		Self::retarget(&state, &params)
+	}
+
+	/// Network hashrate in hashes per second: the work of the blocks of the window
+	/// over the time they took.
+	pub fn hashrate() -> Difficulty {
+		let sums = Self::window_state().sums;
+		if sums.solve_time_sum == 0 {
+			return Difficulty::zero()
+		}
+
+		// The earliest block has no solve time in the window
+		let work = sums.difficulty_sum.saturating_sub(sums.first_difficulty);
+		work.saturating_mul(Difficulty::from(1000u64)) / Difficulty::from(sums.solve_time_sum)
+	}
+
+	// Forget the blocks of the window, the missing blocks count as mined at `difficulty`.
+	// Returns the number of blocks forgotten.
+	fn clear_window(difficulty: Difficulty) -> u32 {
+		let blocks = Self::window_state().len;
+		let _ = <WindowBlocks<T>>::remove_all(None);
+		<Window<T>>::kill();
+		<InitialDifficulty<T>>::put(difficulty);
+		blocks
+	}
+
+	// Difficulty of the block after the window
+	fn retarget(state: &WindowState, params: &RetargetParams) -> Difficulty {
+		let size = T::WindowSize::get();
+		let summary = state.summary(size, Self::initial_difficulty(), params.target_block_time);
+		T::DifficultyAlgorithm::next_difficulty(&summary, params)
+	}
+
+	// Window with a block mined at `now` at `difficulty` after the latest one, and the slot of
+	// the block.
+	fn advance(
+		mut state: WindowState,
+		now: u128,
+		difficulty: Difficulty,
+	) -> (WindowState, Option<u32>) {
+		let slot = state.advance(difficulty, now, T::WindowSize::get(), |slot| {
+			<WindowBlocks<T>>::get(slot)
+				.map(|block| (block.difficulty, block.timestamp.unique_saturated_into()))
+		});
+		(state, slot)
+	}
+}
+
+impl<T: Config> OnTimestampSet<T::Moment> for Pallet<T> {
+	fn on_timestamp_set(now: T::Moment) {
+		// The weight of `Timestamp::set` does not cover the hook, the block pays for the retarget
+		frame_system::Pallet::<T>::register_extra_weight_unchecked(
+			T::WeightInfo::on_timestamp_set(),
+			DispatchClass::Mandatory,
 		);
 
-		<PastDifficultiesAndTimestamps<T>>::put(data);
-		<CurrentDifficulty>::put(difficulty);
+		let params = Self::retarget_params();
+		let old = Self::difficulty();
+
+		let (state, slot) = Self::advance(Self::window_state(), now.unique_saturated_into(), old);
+		if let Some(slot) = slot {
+			let block = DifficultyAndTimestamp { timestamp: now, difficulty: old };
+			<WindowBlocks<T>>::insert(slot, block);
+		}
+
+		let difficulty = Self::retarget(&state, &params);
+
+		<Window<T>>::put(state);
+		<CurrentDifficulty<T>>::put(difficulty);
+		if difficulty != old {
+			Self::deposit_event(Event::DifficultyAdjusted { old, new: difficulty });
+		}
 	}
 }
@@ -0,0 +1,238 @@
+//! Storage migrations of the difficulty pallet.
+
+/// Migration from the `decl_storage!` pallet to the `#[pallet]` one.
+pub mod v1 {
+	use crate::{Config, Pallet};
+	#[cfg(feature = "try-runtime")]
+	use crain_primitives::Difficulty;
+	use frame_support::{
+		storage::migration::move_pallet,
+		traits::{Get, GetStorageVersion, OnRuntimeUpgrade, PalletInfoAccess, StorageVersion},
+		weights::Weight,
+	};
+	use sp_std::marker::PhantomData;
+
+	/// Storage prefix of the `decl_storage!` pallet.
+	pub const OLD_PREFIX: &[u8] = b"Difficulty";
+
+	/// Keeps `PastDifficultiesAndTimestamps`, `CurrentDifficulty` and `InitialDifficulty`
+	/// of a live chain. The values are encoded the same way as before, so they are only moved
+	/// if the pallet is not named `Difficulty` in the runtime.
+	pub struct MigrateToV1<T>(PhantomData<T>);
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV1<T> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T>::on_chain_storage_version() >= 1 {
+				return T::DbWeight::get().reads(1)
+			}
+
+			let name = <Pallet<T> as PalletInfoAccess>::name();
+			// The three values of the pallet
+			let moved = if name.as_bytes() != OLD_PREFIX {
+				move_pallet(OLD_PREFIX, name.as_bytes());
+				T::DbWeight::get().reads_writes(3, 6)
+			} else {
+				0
+			};
+
+			StorageVersion::new(1).put::<Pallet<T>>();
+			moved + T::DbWeight::get().reads_writes(1, 1)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<(), &'static str> {
+			use frame_support::{
+				storage::migration::get_storage_value, traits::OnRuntimeUpgradeHelpersExt,
+			};
+
+			if Pallet::<T>::on_chain_storage_version() >= 1 {
+				return Ok(())
+			}
+			let difficulty = get_storage_value::<Difficulty>(OLD_PREFIX, b"CurrentDifficulty", &[])
+				.ok_or("No `CurrentDifficulty` to migrate")?;
+			Self::set_temp_storage(difficulty, "difficulty");
+			Ok(())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			use frame_support::traits::OnRuntimeUpgradeHelpersExt;
+
+			if Pallet::<T>::on_chain_storage_version() < 1 {
+				return Err("The storage version was not updated")
+			}
+			if !crate::CurrentDifficulty::<T>::exists() {
+				return Err("`CurrentDifficulty` was lost")
+			}
+			match Self::get_temp_storage::<Difficulty>("difficulty") {
+				Some(difficulty) if difficulty != Pallet::<T>::difficulty() =>
+					Err("`CurrentDifficulty` changed"),
+				_ => Ok(()),
+			}
+		}
+	}
+}
+
+/// Storage item of the window before the ring buffer.
+pub const PAST_DIFFICULTIES: &[u8] = b"PastDifficultiesAndTimestamps";
+
+/// Migration of the window from a fixed array to a vector bounded by `WindowSize`.
+pub mod v2 {
+	use super::PAST_DIFFICULTIES;
+	use crate::{Config, DifficultyAndTimestamp, Pallet};
+	use crain_primitives::DIFFICULTY_ADJUST_WINDOW;
+	use frame_support::{
+		storage::migration::{get_storage_value, put_storage_value},
+		traits::{Get, GetStorageVersion, OnRuntimeUpgrade, PalletInfoAccess, StorageVersion},
+		weights::Weight,
+	};
+	use sp_std::{marker::PhantomData, vec::Vec};
+
+	/// Window of the v1 storage, with `None` in place of the blocks before genesis.
+	pub type OldWindow<M> = [Option<DifficultyAndTimestamp<M>>; DIFFICULTY_ADJUST_WINDOW as usize];
+
+	/// Keeps the latest `WindowSize` blocks of the v1 window.
+	pub struct MigrateToV2<T>(PhantomData<T>);
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV2<T> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T>::on_chain_storage_version() != 1 {
+				return T::DbWeight::get().reads(1)
+			}
+
+			let pallet = <Pallet<T> as PalletInfoAccess>::name().as_bytes();
+			if let Some(old) =
+				get_storage_value::<OldWindow<T::Moment>>(pallet, PAST_DIFFICULTIES, &[])
+			{
+				let blocks: Vec<_> = old.into_iter().flatten().collect();
+				let skipped = blocks.len().saturating_sub(T::WindowSize::get() as usize);
+				put_storage_value(pallet, PAST_DIFFICULTIES, &[], blocks[skipped..].to_vec());
+			}
+
+			StorageVersion::new(2).put::<Pallet<T>>();
+			T::DbWeight::get().reads_writes(2, 2)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<(), &'static str> {
+			use frame_support::traits::OnRuntimeUpgradeHelpersExt;
+
+			if Pallet::<T>::on_chain_storage_version() != 1 {
+				return Ok(())
+			}
+			let pallet = <Pallet<T> as PalletInfoAccess>::name().as_bytes();
+			let blocks = get_storage_value::<OldWindow<T::Moment>>(pallet, PAST_DIFFICULTIES, &[])
+				.map_or(0, |old| old.iter().flatten().count());
+			Self::set_temp_storage(blocks.min(T::WindowSize::get() as usize) as u32, "blocks");
+			Ok(())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			use frame_support::traits::OnRuntimeUpgradeHelpersExt;
+
+			if Pallet::<T>::on_chain_storage_version() < 2 {
+				return Err("The storage version was not updated")
+			}
+			// `MigrateToV3` may have moved the window to the ring already
+			let pallet = <Pallet<T> as PalletInfoAccess>::name().as_bytes();
+			let blocks = if Pallet::<T>::on_chain_storage_version() == 2 {
+				get_storage_value::<Vec<DifficultyAndTimestamp<T::Moment>>>(
+					pallet,
+					PAST_DIFFICULTIES,
+					&[],
+				)
+				.map_or(0, |window| window.len() as u32)
+			} else {
+				Pallet::<T>::window_state().len
+			};
+			match Self::get_temp_storage::<u32>("blocks") {
+				Some(expected) if expected != blocks => Err("Blocks of the window were lost"),
+				_ => Ok(()),
+			}
+		}
+	}
+}
+
+/// Migration of the window from a vector to a ring buffer with running sums.
+pub mod v3 {
+	use super::PAST_DIFFICULTIES;
+	use crate::{Config, DifficultyAndTimestamp, Pallet, Window, WindowBlocks, WindowState};
+	use frame_support::{
+		storage::migration::take_storage_value,
+		traits::{Get, GetStorageVersion, OnRuntimeUpgrade, PalletInfoAccess, StorageVersion},
+		weights::Weight,
+	};
+	use sp_runtime::traits::UniqueSaturatedInto;
+	use sp_std::{marker::PhantomData, vec::Vec};
+
+	/// Writes the blocks of the v2 window to the slots of the ring and sums them.
+	pub struct MigrateToV3<T>(PhantomData<T>);
+
+	impl<T: Config> OnRuntimeUpgrade for MigrateToV3<T> {
+		fn on_runtime_upgrade() -> Weight {
+			if Pallet::<T>::on_chain_storage_version() != 2 {
+				return T::DbWeight::get().reads(1)
+			}
+
+			let pallet = <Pallet<T> as PalletInfoAccess>::name().as_bytes();
+			let blocks: Vec<DifficultyAndTimestamp<T::Moment>> =
+				take_storage_value(pallet, PAST_DIFFICULTIES, &[]).unwrap_or_default();
+			let size = T::WindowSize::get();
+			let skipped = blocks.len().saturating_sub(size as usize);
+
+			let mut state = WindowState::default();
+			for block in &blocks[skipped..] {
+				<WindowBlocks<T>>::insert(state.next_slot(size), block);
+				state.push_back(block.difficulty, block.timestamp.unique_saturated_into());
+			}
+			<Window<T>>::put(state);
+
+			StorageVersion::new(3).put::<Pallet<T>>();
+			let blocks = (blocks.len() - skipped) as Weight;
+			T::DbWeight::get().reads_writes(2, blocks + 3)
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn pre_upgrade() -> Result<(), &'static str> {
+			use frame_support::{
+				storage::migration::get_storage_value, traits::OnRuntimeUpgradeHelpersExt,
+			};
+
+			if Pallet::<T>::on_chain_storage_version() != 2 {
+				return Ok(())
+			}
+			let pallet = <Pallet<T> as PalletInfoAccess>::name().as_bytes();
+			let blocks: Vec<DifficultyAndTimestamp<T::Moment>> =
+				get_storage_value(pallet, PAST_DIFFICULTIES, &[]).unwrap_or_default();
+			let kept = blocks.len().min(T::WindowSize::get() as usize);
+			let latest: Option<u128> =
+				blocks.last().map(|block| block.timestamp.unique_saturated_into());
+			Self::set_temp_storage((kept as u32, latest), "window");
+			Ok(())
+		}
+
+		#[cfg(feature = "try-runtime")]
+		fn post_upgrade() -> Result<(), &'static str> {
+			use frame_support::traits::OnRuntimeUpgradeHelpersExt;
+
+			if Pallet::<T>::on_chain_storage_version() < 3 {
+				return Err("The storage version was not updated")
+			}
+			let state = Pallet::<T>::window_state();
+			if state.len > T::WindowSize::get() {
+				return Err("The window is larger than `WindowSize`")
+			}
+			let window = Self::get_temp_storage::<(u32, Option<u128>)>("window");
+			if let Some((blocks, latest)) = window {
+				if state.len != blocks {
+					return Err("Blocks of the window were lost")
+				}
+				if latest.map_or(false, |latest| latest != state.latest_timestamp) {
+					return Err("The latest timestamp of the window changed")
+				}
+			}
+			Ok(())
+		}
+	}
+}
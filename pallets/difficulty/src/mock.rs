@@ -0,0 +1,124 @@
+use crate as pallet_difficulty;
+use crain_primitives::{hash::HashAlgorithm, Difficulty, MAX_DIFFICULTY, MIN_DIFFICULTY};
+use frame_support::{
+	parameter_types,
+	traits::{ConstU32, ConstU64, GenesisBuild, OnTimestampSet},
+};
+use sp_core::H256;
+use sp_runtime::{
+	testing::Header,
+	traits::{BlakeTwo256, IdentityLookup},
+};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+pub const TARGET_BLOCK_TIME: u64 = 1000;
+pub const WINDOW: u32 = 10;
+pub const INITIAL_DIFFICULTY: u128 = 1_000_000;
+/// Timestamp of the first block mined by [`mine_blocks`].
+pub const GENESIS_TIMESTAMP: u64 = 1_000_000;
+
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system,
+		Timestamp: pallet_timestamp,
+		DifficultyModule: pallet_difficulty,
+	}
+);
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type DbWeight = ();
+	type Origin = Origin;
+	type Call = Call;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = u64;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = Event;
+	type BlockHashCount = ConstU64<250>;
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type AccountData = ();
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = ();
+	type OnSetCode = ();
+	type MaxConsumers = ConstU32<16>;
+}
+
+impl pallet_timestamp::Config for Test {
+	type Moment = u64;
+	type OnTimestampSet = DifficultyModule;
+	type MinimumPeriod = ConstU64<1>;
+	type WeightInfo = ();
+}
+
+parameter_types! {
+	pub static WindowSize: u32 = WINDOW;
+	pub static DampFactor: u128 = 3;
+	pub static ClampFactor: u128 = 2;
+	pub static MinDifficulty: u128 = MIN_DIFFICULTY;
+	pub static MaxDifficulty: u128 = MAX_DIFFICULTY;
+}
+
+impl pallet_difficulty::Config for Test {
+	type Event = Event;
+	type TargetBlockTime = ConstU64<TARGET_BLOCK_TIME>;
+	type DifficultyAlgorithm = pallet_difficulty::DampClamp;
+	type WindowSize = WindowSize;
+	type DampFactor = DampFactor;
+	type ClampFactor = ClampFactor;
+	type HalfLife = ConstU32<WINDOW>;
+	type MinDifficulty = MinDifficulty;
+	type MaxDifficulty = MaxDifficulty;
+	type WeightInfo = ();
+}
+
+/// Set the timestamp of a block to `now`, the way `pallet_timestamp` does, without its check
+/// that time moves forward.
+pub fn set_timestamp(now: u64) {
+	<DifficultyModule as OnTimestampSet<u64>>::on_timestamp_set(now);
+}
+
+/// Mine a block at `GENESIS_TIMESTAMP`, then one after each of the solve times.
+/// Returns the timestamp of the last block.
+pub fn mine_blocks(solve_times: impl IntoIterator<Item = u64>) -> u64 {
+	let mut now = GENESIS_TIMESTAMP;
+	set_timestamp(now);
+	for solve_time in solve_times {
+		now = now.saturating_add(solve_time);
+		set_timestamp(now);
+	}
+	now
+}
+
+/// Difficulty of the next block.
+pub fn difficulty() -> Difficulty {
+	DifficultyModule::difficulty()
+}
+
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let mut storage = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	pallet_difficulty::GenesisConfig {
+		initial_difficulty: INITIAL_DIFFICULTY.into(),
+		pow_algorithm: HashAlgorithm::Blake2b,
+	}
+	.assimilate_storage::<Test>(&mut storage)
+	.unwrap();
+
+	let mut ext = sp_io::TestExternalities::new(storage);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
@@ -0,0 +1,435 @@
+use crate::{
+	migrations::{v1, v2, v3, PAST_DIFFICULTIES},
+	mock::*,
+	DampClamp, DifficultyAlgorithm, DifficultyAndTimestamp, Error, Event as DifficultyEvent,
+	WeightInfo, WindowState, WindowSummary,
+};
+use crain_primitives::{hash::HashAlgorithm, Difficulty, DIFFICULTY_ADJUST_WINDOW};
+use frame_support::{
+	assert_noop, assert_ok,
+	storage::migration::{get_storage_value, put_storage_value},
+	traits::{GetStorageVersion, Hooks, OnRuntimeUpgrade, StorageVersion},
+	weights::DispatchClass,
+};
+use proptest::prelude::*;
+use sp_runtime::DispatchError;
+
+const T: u64 = TARGET_BLOCK_TIME;
+
+fn initial() -> Difficulty {
+	Difficulty::from(INITIAL_DIFFICULTY)
+}
+
+// Summary of the blocks of a full window
+fn summary(blocks: &[DifficultyAndTimestamp<u64>]) -> WindowSummary {
+	let mut summary = WindowSummary::default();
+	let mut previous = blocks[0].timestamp;
+	for block in blocks {
+		summary.push(block.difficulty, (block.timestamp - previous).into());
+		previous = block.timestamp;
+	}
+	summary
+}
+
+#[test]
+fn genesis_sets_initial_difficulty() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(difficulty(), initial());
+		assert_eq!(DifficultyModule::initial_difficulty(), initial());
+		assert!(DifficultyModule::window().is_empty());
+		assert_eq!(DifficultyModule::pow_algorithm(), HashAlgorithm::Blake2b);
+	});
+}
+
+#[test]
+fn block_is_added_to_window() {
+	new_test_ext().execute_with(|| {
+		set_timestamp(GENESIS_TIMESTAMP);
+
+		assert_eq!(
+			DifficultyModule::window(),
+			vec![DifficultyAndTimestamp { difficulty: initial(), timestamp: GENESIS_TIMESTAMP }],
+		);
+		assert_eq!(DifficultyModule::window_state().len, 1);
+	});
+}
+
+#[test]
+fn window_keeps_latest_blocks() {
+	new_test_ext().execute_with(|| {
+		mine_blocks([T; 24]);
+
+		let timestamps: Vec<_> =
+			DifficultyModule::window().iter().map(|block| block.timestamp).collect();
+		let expected: Vec<_> = (15..25).map(|block| GENESIS_TIMESTAMP + block * T).collect();
+		assert_eq!(timestamps, expected);
+		assert_eq!(DifficultyModule::window_state().len, WINDOW);
+	});
+}
+
+#[test]
+fn retarget_runs_the_algorithm_on_the_window() {
+	new_test_ext().execute_with(|| {
+		mine_blocks((0..25).map(|block| block * 137 % (3 * T)));
+
+		let window = DifficultyModule::window();
+		let params = DifficultyModule::retarget_params();
+		assert_eq!(difficulty(), DampClamp::next_difficulty(&summary(&window), &params));
+	});
+}
+
+#[test]
+fn fast_blocks_raise_difficulty() {
+	new_test_ext().execute_with(|| {
+		mine_blocks([T / 4; WINDOW as usize]);
+
+		assert!(difficulty() > initial());
+	});
+}
+
+#[test]
+fn slow_blocks_lower_difficulty() {
+	new_test_ext().execute_with(|| {
+		mine_blocks([4 * T; 2 * WINDOW as usize]);
+
+		assert!(difficulty() < initial());
+	});
+}
+
+#[test]
+fn equal_timestamps_raise_difficulty() {
+	new_test_ext().execute_with(|| {
+		mine_blocks([0; 3 * WINDOW as usize]);
+
+		assert!(difficulty() > initial());
+	});
+}
+
+#[test]
+fn timestamp_going_back_is_handled() {
+	new_test_ext().execute_with(|| {
+		let now = mine_blocks([T; WINDOW as usize]);
+		set_timestamp(now - 5 * T);
+		set_timestamp(0);
+
+		assert_eq!(DifficultyModule::window().last().map(|block| block.timestamp), Some(0));
+		assert!(difficulty() > initial());
+	});
+}
+
+#[test]
+fn retarget_weight_is_registered() {
+	new_test_ext().execute_with(|| {
+		let before = *System::block_weight().get(DispatchClass::Mandatory);
+		set_timestamp(GENESIS_TIMESTAMP);
+
+		assert_eq!(
+			*System::block_weight().get(DispatchClass::Mandatory),
+			before + <() as WeightInfo>::on_timestamp_set(),
+		);
+	});
+}
+
+#[test]
+fn difficulty_change_emits_event() {
+	new_test_ext().execute_with(|| {
+		set_timestamp(GENESIS_TIMESTAMP);
+
+		System::assert_last_event(
+			DifficultyEvent::DifficultyAdjusted { old: initial(), new: difficulty() }.into(),
+		);
+	});
+}
+
+#[test]
+fn difficulty_is_bounded() {
+	new_test_ext().execute_with(|| {
+		MaxDifficulty::set(INITIAL_DIFFICULTY + 1);
+		mine_blocks([0; WINDOW as usize]);
+		assert_eq!(difficulty(), Difficulty::from(INITIAL_DIFFICULTY + 1));
+
+		MinDifficulty::set(INITIAL_DIFFICULTY);
+		mine_blocks([100 * T; 2 * WINDOW as usize]);
+		assert_eq!(difficulty(), initial());
+	});
+}
+
+#[test]
+fn integrity_test_passes() {
+	DifficultyModule::integrity_test();
+}
+
+#[test]
+#[should_panic(expected = "`DampFactor` must be at least 1")]
+fn integrity_test_rejects_zero_damp_factor() {
+	DampFactor::set(0);
+	DifficultyModule::integrity_test();
+}
+
+#[test]
+#[should_panic(expected = "`ClampFactor` must be at least 1")]
+fn integrity_test_rejects_zero_clamp_factor() {
+	ClampFactor::set(0);
+	DifficultyModule::integrity_test();
+}
+
+#[test]
+#[should_panic(expected = "`MinDifficulty` must not be higher than `MaxDifficulty`")]
+fn integrity_test_rejects_inverted_bounds() {
+	MinDifficulty::set(MaxDifficulty::get() / 2 + 1);
+	MaxDifficulty::set(MaxDifficulty::get() / 2);
+	DifficultyModule::integrity_test();
+}
+
+#[test]
+#[should_panic(expected = "`WindowSize` must not be 0")]
+fn integrity_test_rejects_empty_window() {
+	WindowSize::set(0);
+	DifficultyModule::integrity_test();
+}
+
+#[test]
+fn predicted_difficulty_is_retarget_on_target() {
+	new_test_ext().execute_with(|| {
+		let now = mine_blocks((0..15).map(|block| block * 211 % (2 * T)));
+		let predicted = DifficultyModule::predicted_difficulty();
+
+		set_timestamp(now + T);
+		assert_eq!(difficulty(), predicted);
+	});
+}
+
+#[test]
+fn hashrate_is_work_over_time() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(DifficultyModule::hashrate(), Difficulty::zero());
+
+		mine_blocks([T; 2 * WINDOW as usize]);
+
+		let window = DifficultyModule::window();
+		let work =
+			window[1..].iter().fold(Difficulty::zero(), |work, block| work + block.difficulty);
+		let time = window[window.len() - 1].timestamp - window[0].timestamp;
+		assert_eq!(
+			DifficultyModule::hashrate(),
+			work * Difficulty::from(1000) / Difficulty::from(time),
+		);
+	});
+}
+
+#[test]
+fn set_difficulty_works() {
+	new_test_ext().execute_with(|| {
+		let new = Difficulty::from(42);
+		assert_ok!(DifficultyModule::set_difficulty(Origin::root(), new));
+
+		assert_eq!(difficulty(), new);
+		System::assert_last_event(DifficultyEvent::DifficultySet { old: initial(), new }.into());
+	});
+}
+
+#[test]
+fn set_difficulty_is_kept_by_the_retarget() {
+	new_test_ext().execute_with(|| {
+		let mut now = mine_blocks([T / 4; 2 * WINDOW as usize]);
+		let raised = difficulty();
+		let new = raised / 4;
+		assert_ok!(DifficultyModule::set_difficulty(Origin::root(), new));
+
+		assert_eq!(DifficultyModule::window_state(), WindowState::default());
+		assert_eq!(DifficultyModule::initial_difficulty(), new);
+
+		// Blocks on target keep the difficulty around the one that was set, the blocks
+		// mined before it do not pull it back up
+		for _ in 0..WINDOW {
+			now += T;
+			set_timestamp(now);
+			assert!(difficulty() > new / 2 && difficulty() < new * 2, "{}", difficulty());
+		}
+	});
+}
+
+#[test]
+fn set_difficulty_needs_root() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			DifficultyModule::set_difficulty(Origin::signed(1), 42.into()),
+			DispatchError::BadOrigin,
+		);
+	});
+}
+
+#[test]
+fn set_difficulty_is_bounded() {
+	new_test_ext().execute_with(|| {
+		MaxDifficulty::set(1000);
+
+		assert_noop!(
+			DifficultyModule::set_difficulty(Origin::root(), 1001.into()),
+			Error::<Test>::DifficultyOutOfBounds,
+		);
+		assert_noop!(
+			DifficultyModule::set_difficulty(Origin::root(), 1.into()),
+			Error::<Test>::DifficultyOutOfBounds,
+		);
+	});
+}
+
+#[test]
+fn reset_window_works() {
+	new_test_ext().execute_with(|| {
+		let now = mine_blocks([T / 2; 2 * WINDOW as usize]);
+		let current = difficulty();
+		assert_ok!(DifficultyModule::reset_window(Origin::root()));
+
+		assert!(DifficultyModule::window().is_empty());
+		assert_eq!(DifficultyModule::window_state(), WindowState::default());
+		assert_eq!(DifficultyModule::initial_difficulty(), current);
+		System::assert_last_event(DifficultyEvent::WindowReset { blocks: WINDOW }.into());
+
+		// The missing blocks are padded with the difficulty at the reset
+		set_timestamp(now + T);
+		let params = DifficultyModule::retarget_params();
+		let mut padded = WindowState::default();
+		padded.push_back(current, (now + T).into());
+		let summary = padded.summary(WINDOW, current, T.into());
+		assert_eq!(difficulty(), DampClamp::next_difficulty(&summary, &params));
+	});
+}
+
+#[test]
+fn reset_window_needs_root() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(DifficultyModule::reset_window(Origin::signed(1)), DispatchError::BadOrigin);
+	});
+}
+
+#[test]
+fn v1_moves_renamed_pallet() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(0).put::<DifficultyModule>();
+		put_storage_value(v1::OLD_PREFIX, b"CurrentDifficulty", &[], Difficulty::from(42));
+
+		v1::MigrateToV1::<Test>::on_runtime_upgrade();
+
+		assert_eq!(difficulty(), Difficulty::from(42));
+		assert_eq!(
+			get_storage_value::<Difficulty>(v1::OLD_PREFIX, b"CurrentDifficulty", &[]),
+			None,
+		);
+		assert_eq!(DifficultyModule::on_chain_storage_version(), 1);
+	});
+}
+
+#[test]
+fn v2_and_v3_fill_the_ring_from_the_array() {
+	new_test_ext().execute_with(|| {
+		StorageVersion::new(1).put::<DifficultyModule>();
+		let blocks: Vec<_> = (0..15u64)
+			.map(|block| DifficultyAndTimestamp {
+				difficulty: Difficulty::from(1000 + block),
+				timestamp: GENESIS_TIMESTAMP + block * T,
+			})
+			.collect();
+		let mut old: v2::OldWindow<u64> = [None; DIFFICULTY_ADJUST_WINDOW as usize];
+		let offset = old.len() - blocks.len();
+		for (entry, block) in old[offset..].iter_mut().zip(&blocks) {
+			*entry = Some(*block);
+		}
+		put_storage_value(b"DifficultyModule", PAST_DIFFICULTIES, &[], old);
+
+		v2::MigrateToV2::<Test>::on_runtime_upgrade();
+		v3::MigrateToV3::<Test>::on_runtime_upgrade();
+
+		let kept = &blocks[blocks.len() - WINDOW as usize..];
+		let mut expected = WindowState::default();
+		for block in kept {
+			expected.push_back(block.difficulty, block.timestamp.into());
+		}
+		assert_eq!(DifficultyModule::window(), kept.to_vec());
+		assert_eq!(DifficultyModule::window_state(), expected);
+		assert_eq!(DifficultyModule::on_chain_storage_version(), 3);
+		assert_eq!(
+			get_storage_value::<Vec<DifficultyAndTimestamp<u64>>>(
+				b"DifficultyModule",
+				PAST_DIFFICULTIES,
+				&[],
+			),
+			None,
+		);
+	});
+}
+
+// Timestamps with many zeros, repeats and the largest one
+fn timestamp() -> impl Strategy<Value = u64> {
+	prop_oneof![Just(0), Just(GENESIS_TIMESTAMP), Just(u64::MAX), any::<u64>()]
+}
+
+// Lowest and highest difficulty, the lowest one being at most the highest one
+fn bounds() -> impl Strategy<Value = (u128, u128)> {
+	(any::<u128>(), any::<u128>()).prop_map(|(a, b)| (a.min(b), a.max(b)))
+}
+
+proptest! {
+	#[test]
+	fn difficulty_stays_in_bounds(
+		solve_times in prop::collection::vec(0..10 * T, 0..3 * WINDOW as usize),
+		(min, max) in bounds(),
+		window in 1..=2 * WINDOW,
+	) {
+		new_test_ext().execute_with(|| {
+			MinDifficulty::set(min);
+			MaxDifficulty::set(max);
+			WindowSize::set(window);
+
+			let mut now = GENESIS_TIMESTAMP;
+			for solve_time in solve_times {
+				now += solve_time;
+				set_timestamp(now);
+				prop_assert!(difficulty() >= Difficulty::from(min));
+				prop_assert!(difficulty() <= Difficulty::from(max));
+			}
+			Ok(())
+		})?;
+	}
+
+	#[test]
+	fn fast_blocks_always_raise_difficulty(
+		solve_times in prop::collection::vec(0..=T / 2, 0..3 * WINDOW as usize),
+	) {
+		new_test_ext().execute_with(|| {
+			mine_blocks(solve_times);
+			prop_assert!(difficulty() > initial());
+			Ok(())
+		})?;
+	}
+
+	#[test]
+	fn slow_blocks_always_lower_difficulty(
+		solve_times in prop::collection::vec(
+			2 * T..=10 * T,
+			2 * WINDOW as usize..3 * WINDOW as usize,
+		),
+	) {
+		new_test_ext().execute_with(|| {
+			mine_blocks(solve_times);
+			prop_assert!(difficulty() < initial());
+			Ok(())
+		})?;
+	}
+
+	#[test]
+	fn any_timestamps_are_handled(
+		timestamps in prop::collection::vec(timestamp(), 0..3 * WINDOW as usize),
+	) {
+		new_test_ext().execute_with(|| {
+			for now in timestamps {
+				set_timestamp(now);
+				prop_assert!(difficulty() >= Difficulty::from(MinDifficulty::get()));
+			}
+			DifficultyModule::predicted_difficulty();
+			DifficultyModule::hashrate();
+			Ok(())
+		})?;
+	}
+}
@@ -0,0 +1,101 @@
+//! Weights for pallet_difficulty
+//!
+//! Hand-counted, not benchmark output: every function lists the storage its benchmark touches,
+//! the constant parts are rough estimates. Overwrite this file with:
+//!
+//! ./target/release/crain-node benchmark pallet --chain dev --execution wasm
+//! --wasm-execution compiled --pallet pallet_difficulty --extrinsic '*' --steps 50 --repeat 20
+//! --output ./pallets/difficulty/src/weights.rs
+
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{
+	traits::Get,
+	weights::{constants::RocksDbWeight, Weight},
+};
+use sp_std::marker::PhantomData;
+
+/// Weight functions needed for pallet_difficulty.
+pub trait WeightInfo {
+	fn on_timestamp_set() -> Weight;
+	fn set_difficulty(b: u32) -> Weight;
+	fn reset_window(b: u32) -> Weight;
+}
+
+/// Weights for pallet_difficulty using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	// Storage: System BlockWeight (r:1 w:1)
+	// Storage: Difficulty CurrentDifficulty (r:1 w:1)
+	// Storage: Difficulty Window (r:1 w:1)
+	// Storage: Difficulty WindowBlocks (r:2 w:1)
+	// Storage: Difficulty InitialDifficulty (r:1 w:0)
+	// Storage: System EventCount (r:1 w:1)
+	// Storage: System Events (r:1 w:1)
+	fn on_timestamp_set() -> Weight {
+		(24_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(8 as Weight))
+			.saturating_add(T::DbWeight::get().writes(6 as Weight))
+	}
+	// Storage: Difficulty CurrentDifficulty (r:1 w:1)
+	// Storage: Difficulty Window (r:1 w:1)
+	// Storage: Difficulty WindowBlocks (r:0 w:1)
+	// Storage: Difficulty InitialDifficulty (r:0 w:1)
+	fn set_difficulty(b: u32) -> Weight {
+		(15_000_000 as Weight)
+			.saturating_add((1_000_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(b as Weight)))
+	}
+	// Storage: Difficulty Window (r:1 w:1)
+	// Storage: Difficulty WindowBlocks (r:0 w:1)
+	// Storage: Difficulty CurrentDifficulty (r:1 w:0)
+	// Storage: Difficulty InitialDifficulty (r:0 w:1)
+	fn reset_window(b: u32) -> Weight {
+		(15_000_000 as Weight)
+			.saturating_add((1_000_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes(2 as Weight))
+			.saturating_add(T::DbWeight::get().writes((1 as Weight).saturating_mul(b as Weight)))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	// Storage: System BlockWeight (r:1 w:1)
+	// Storage: Difficulty CurrentDifficulty (r:1 w:1)
+	// Storage: Difficulty Window (r:1 w:1)
+	// Storage: Difficulty WindowBlocks (r:2 w:1)
+	// Storage: Difficulty InitialDifficulty (r:1 w:0)
+	// Storage: System EventCount (r:1 w:1)
+	// Storage: System Events (r:1 w:1)
+	fn on_timestamp_set() -> Weight {
+		(24_000_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(8 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(6 as Weight))
+	}
+	// Storage: Difficulty CurrentDifficulty (r:1 w:1)
+	// Storage: Difficulty Window (r:1 w:1)
+	// Storage: Difficulty WindowBlocks (r:0 w:1)
+	// Storage: Difficulty InitialDifficulty (r:0 w:1)
+	fn set_difficulty(b: u32) -> Weight {
+		(15_000_000 as Weight)
+			.saturating_add((1_000_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add(RocksDbWeight::get().reads(2 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(3 as Weight))
+			.saturating_add(RocksDbWeight::get().writes((1 as Weight).saturating_mul(b as Weight)))
+	}
+	// Storage: Difficulty Window (r:1 w:1)
+	// Storage: Difficulty WindowBlocks (r:0 w:1)
+	// Storage: Difficulty CurrentDifficulty (r:1 w:0)
+	// Storage: Difficulty InitialDifficulty (r:0 w:1)
+	fn reset_window(b: u32) -> Weight {
+		(15_000_000 as Weight)
+			.saturating_add((1_000_000 as Weight).saturating_mul(b as Weight))
+			.saturating_add(RocksDbWeight::get().reads(2 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(2 as Weight))
+			.saturating_add(RocksDbWeight::get().writes((1 as Weight).saturating_mul(b as Weight)))
+	}
+}
@@ -0,0 +1,288 @@
+//! Retarget window kept as a ring buffer of blocks with running sums, so a retarget costs
+//! the same whatever the size of the window.
+
+use crate::algorithm::WindowSummary;
+use crain_primitives::Difficulty;
+use parity_scale_codec::{Decode, Encode, MaxEncodedLen};
+use scale_info::TypeInfo;
+
+/// Position of the blocks of the window in the ring and running sums over them.
+#[derive(Encode, Decode, TypeInfo, MaxEncodedLen, Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct WindowState {
+	/// Slot of the earliest block.
+	pub first: u32,
+	/// Number of blocks in the window.
+	pub len: u32,
+	/// Timestamp of the latest block.
+	pub latest_timestamp: u128,
+	/// Running sums over the blocks in the window.
+	pub sums: WindowSummary,
+}
+
+impl WindowState {
+	/// Slot the next block is written to, in a ring of `size` slots.
+	pub fn next_slot(&self, size: u32) -> u32 {
+		(self.first + self.len) % size.max(1)
+	}
+
+	/// Slot of the `index`th block from the earliest one, in a ring of `size` slots.
+	pub fn slot(&self, index: u32, size: u32) -> u32 {
+		(self.first + index) % size.max(1)
+	}
+
+	/// Remove the earliest block, mined at `timestamp`, from a ring of `size` slots.
+	/// `next` is the difficulty and the timestamp of the block after it, if there is one.
+	pub fn pop_front(&mut self, timestamp: u128, next: Option<(Difficulty, u128)>, size: u32) {
+		if self.len == 0 {
+			return
+		}
+
+		match next {
+			Some((difficulty, next_timestamp)) if self.len > 1 => {
+				let sums = &mut self.sums;
+				// Every remaining solve time moves one position toward the start
+				sums.weighted_solve_time_sum =
+					sums.weighted_solve_time_sum.saturating_sub(sums.solve_time_sum);
+				sums.solve_time_sum =
+					sums.solve_time_sum.saturating_sub(next_timestamp.saturating_sub(timestamp));
+				sums.difficulty_sum = sums.difficulty_sum.saturating_sub(sums.first_difficulty);
+				sums.first_difficulty = difficulty;
+				sums.blocks -= 1;
+			},
+			_ => self.sums = WindowSummary::default(),
+		}
+
+		self.first = self.slot(1, size);
+		self.len -= 1;
+	}
+
+	/// Add a block after the latest one.
+	pub fn push_back(&mut self, difficulty: Difficulty, timestamp: u128) {
+		let solve_time =
+			if self.len == 0 { 0 } else { timestamp.saturating_sub(self.latest_timestamp) };
+		self.sums.push(difficulty, solve_time);
+		self.latest_timestamp = timestamp;
+		self.len += 1;
+	}
+
+	/// Add a block mined at `timestamp` at `difficulty` after the latest one, in a ring of `size`
+	/// slots. A full window first evicts its earliest block, whose slot is reused. `block` gives
+	/// the difficulty and the timestamp of the block in a slot. Returns the slot to write the
+	/// new block to, none if the ring has no slot.
+	pub fn advance(
+		&mut self,
+		difficulty: Difficulty,
+		timestamp: u128,
+		size: u32,
+		block: impl Fn(u32) -> Option<(Difficulty, u128)>,
+	) -> Option<u32> {
+		if size == 0 {
+			return None
+		}
+
+		if self.len >= size {
+			let earliest = block(self.first).map(|(_, timestamp)| timestamp);
+			let next = block(self.slot(1, size));
+			self.pop_front(earliest.unwrap_or_default(), next, size);
+		}
+
+		let slot = self.next_slot(size);
+		self.push_back(difficulty, timestamp);
+		Some(slot)
+	}
+
+	/// Summary of a window of `size` blocks, with `initial_difficulty` and `block_time`
+	/// in place of the blocks before genesis.
+	pub fn summary(
+		&self,
+		size: u32,
+		initial_difficulty: Difficulty,
+		block_time: u128,
+	) -> WindowSummary {
+		let missing = size.saturating_sub(self.len);
+		if missing == 0 {
+			return self.sums
+		}
+
+		// Solve times of the blocks before genesis, then of the earliest block if there is one
+		let padded = u128::from(if self.len == 0 { missing - 1 } else { missing });
+		let padded_sum = padded.saturating_mul(block_time);
+		let padded_weighted = (padded * (padded + 1) / 2).saturating_mul(block_time);
+		// The solve times in the window are `missing` positions further from the start
+		let shifted_weighted = self.sums.solve_time_sum.saturating_mul(missing as u128);
+
+		WindowSummary {
+			blocks: missing + self.len,
+			first_difficulty: initial_difficulty,
+			difficulty_sum: initial_difficulty
+				.saturating_mul(missing.into())
+				.saturating_add(self.sums.difficulty_sum),
+			solve_time_sum: padded_sum.saturating_add(self.sums.solve_time_sum),
+			weighted_solve_time_sum: padded_weighted
+				.saturating_add(self.sums.weighted_solve_time_sum)
+				.saturating_add(shifted_weighted),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::algorithm::{DampClamp, DifficultyAlgorithm, RetargetParams};
+	use crate::{clamp, damp};
+	use crain_primitives::{
+		CLAMP_FACTOR, DIFFICULTY_ADJUST_WINDOW, DIFFICULTY_DAMP_FACTOR, DIFFICULTY_HALF_LIFE,
+		MAX_DIFFICULTY, MIN_DIFFICULTY,
+	};
+	use sp_std::cmp::{max, min};
+
+	const WINDOW: usize = DIFFICULTY_ADJUST_WINDOW as usize;
+	const BLOCK_TIME: u128 = 60_000;
+	const INITIAL_DIFFICULTY: u128 = 1_000_000;
+
+	const PARAMS: RetargetParams = RetargetParams {
+		target_block_time: BLOCK_TIME,
+		damp_factor: DIFFICULTY_DAMP_FACTOR,
+		clamp_factor: CLAMP_FACTOR,
+		half_life: DIFFICULTY_HALF_LIFE,
+		min_difficulty: MIN_DIFFICULTY,
+		max_difficulty: MAX_DIFFICULTY,
+	};
+
+	/// The retarget before the ring buffer: shift the whole array and sum it again.
+	fn array_retarget(
+		data: &mut [Option<(Difficulty, u128)>; WINDOW],
+		difficulty: Difficulty,
+		now: u128,
+	) -> Difficulty {
+		let block_time_window = WINDOW as u128 * BLOCK_TIME;
+
+		for i in 1..data.len() {
+			data[i - 1] = data[i];
+		}
+		data[WINDOW - 1] = Some((difficulty, now));
+
+		let mut ts_delta = 0;
+		for i in 1..WINDOW {
+			let delta = match (data[i - 1], data[i]) {
+				(Some((_, prev)), Some((_, cur))) => cur.saturating_sub(prev),
+				_ => BLOCK_TIME,
+			};
+			ts_delta += delta;
+		}
+		if ts_delta == 0 {
+			ts_delta = 1;
+		}
+
+		let mut diff_sum = Difficulty::zero();
+		for entry in data.iter() {
+			diff_sum += entry.map(|(d, _)| d).unwrap_or_else(|| INITIAL_DIFFICULTY.into());
+		}
+		if diff_sum < Difficulty::from(MIN_DIFFICULTY) {
+			diff_sum = Difficulty::from(MIN_DIFFICULTY);
+		}
+
+		let adj_ts = clamp(
+			damp(ts_delta, block_time_window, DIFFICULTY_DAMP_FACTOR),
+			block_time_window,
+			CLAMP_FACTOR,
+		);
+		min(
+			Difficulty::from(MAX_DIFFICULTY),
+			max(
+				Difficulty::from(MIN_DIFFICULTY),
+				diff_sum * Difficulty::from(BLOCK_TIME) / Difficulty::from(adj_ts),
+			),
+		)
+	}
+
+	/// The retarget with the ring, the slots are kept in `ring`.
+	fn ring_retarget(
+		state: &mut WindowState,
+		ring: &mut [(Difficulty, u128); WINDOW],
+		difficulty: Difficulty,
+		now: u128,
+	) -> Difficulty {
+		let size = WINDOW as u32;
+		let slot = state.advance(difficulty, now, size, |slot| Some(ring[slot as usize]));
+		ring[slot.unwrap() as usize] = (difficulty, now);
+
+		DampClamp::next_difficulty(
+			&state.summary(size, INITIAL_DIFFICULTY.into(), BLOCK_TIME),
+			&PARAMS,
+		)
+	}
+
+	// Solve times around the target, with bursts of equal timestamps and a clock going back
+	fn solve_times(blocks: usize) -> impl Iterator<Item = u128> {
+		let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+		(0..blocks).map(move |block| {
+			seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+			match block % 50 {
+				10..=14 | 30 => 0,
+				_ => (seed >> 33) as u128 % (4 * BLOCK_TIME),
+			}
+		})
+	}
+
+	#[test]
+	fn ring_matches_array() {
+		let mut array = [None; WINDOW];
+		let mut ring = [(Difficulty::zero(), 0); WINDOW];
+		let mut state = WindowState::default();
+
+		let mut array_difficulty = Difficulty::from(INITIAL_DIFFICULTY);
+		let mut ring_difficulty = array_difficulty;
+		let mut now = 1_000_000_000u128;
+		for (block, solve_time) in solve_times(5 * WINDOW).enumerate() {
+			now = if block % 50 == 30 { now - BLOCK_TIME } else { now + solve_time };
+
+			array_difficulty = array_retarget(&mut array, array_difficulty, now);
+			ring_difficulty = ring_retarget(&mut state, &mut ring, ring_difficulty, now);
+			assert_eq!(array_difficulty, ring_difficulty, "block {}", block);
+		}
+	}
+
+	#[test]
+	fn summary_pads_missing_blocks() {
+		let initial = Difficulty::from(INITIAL_DIFFICULTY);
+		let mut state = WindowState::default();
+		for (difficulty, timestamp) in [(5u64, 1000), (6, 1500), (7, 4000)] {
+			state.push_back(difficulty.into(), timestamp);
+		}
+
+		let mut expected = WindowSummary::default();
+		for _ in 0..WINDOW - 3 {
+			expected.push(initial, BLOCK_TIME);
+		}
+		expected.push(5.into(), BLOCK_TIME);
+		expected.push(6.into(), 500);
+		expected.push(7.into(), 2500);
+
+		assert_eq!(state.summary(WINDOW as u32, initial, BLOCK_TIME), expected);
+	}
+
+	#[test]
+	fn empty_window_is_padded() {
+		let initial = Difficulty::from(INITIAL_DIFFICULTY);
+		let mut expected = WindowSummary::default();
+		for _ in 0..WINDOW {
+			expected.push(initial, BLOCK_TIME);
+		}
+
+		assert_eq!(WindowState::default().summary(WINDOW as u32, initial, BLOCK_TIME), expected);
+	}
+
+	#[test]
+	fn ring_wraps_around() {
+		let mut state = WindowState::default();
+		for timestamp in 0..3 {
+			state.push_back(1.into(), timestamp);
+		}
+		state.pop_front(0, Some((1.into(), 1)), 3);
+
+		assert_eq!(state.first, 1);
+		assert_eq!(state.next_slot(3), 0);
+		assert_eq!(state.slot(1, 3), 2);
+	}
+}
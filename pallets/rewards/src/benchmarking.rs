@@ -0,0 +1,75 @@
+//! Benchmarks of the rewards pallet.
+
+use super::*;
+use frame_benchmarking::{account, benchmarks, impl_benchmark_test_suite};
+use frame_support::traits::Hooks;
+use pallet_authorship::EventHandler;
+
+// Reward `accounts` accounts in every block of the maturity period, so that every account has
+// a full list of immature rewards. Returns the block the first rewards mature at.
+fn reward_accounts<T: Config>(accounts: u32) -> BlockNumberFor<T> {
+	let reward = T::Currency::minimum_balance().saturating_mul(10u32.into());
+	let first: BlockNumberFor<T> = One::one();
+	let mature_at = first + T::RewardMaturity::get();
+
+	let mut block = first;
+	while block < mature_at {
+		<frame_system::Pallet<T>>::set_block_number(block);
+		for index in 0..accounts {
+			Pallet::<T>::mint_locked(&account("miner", index, 0), reward);
+		}
+		block += One::one();
+	}
+
+	mature_at
+}
+
+fn assert_last_event<T: Config>(event: Event<T>) {
+	<frame_system::Pallet<T>>::assert_last_event(<T as Config>::Event::from(event).into());
+}
+
+benchmarks! {
+	// Unlocks the rewards of `m` accounts, each with a full list of immature rewards
+	on_initialize {
+		let m in 0 .. MaxRewardedPerBlock::get();
+		let now = reward_accounts::<T>(m);
+		<frame_system::Pallet<T>>::set_block_number(now);
+	}: {
+		Pallet::<T>::on_initialize(now);
+	}
+	verify {
+		assert!(<Maturing<T>>::get(now).is_empty());
+	}
+
+	// Mints the block reward to an author who already has a full list of immature rewards
+	on_finalize {
+		let now = reward_accounts::<T>(1);
+		<frame_system::Pallet<T>>::set_block_number(now);
+		Pallet::<T>::on_initialize(now);
+		let author: T::AccountId = account("miner", 0, 0);
+		<Author<T>>::put(author.clone());
+		let reward = Pallet::<T>::subsidy(now, Pallet::<T>::minted());
+	}: {
+		Pallet::<T>::on_finalize(now);
+	}
+	verify {
+		assert!(<Author<T>>::get().is_none());
+		assert_last_event::<T>(Event::Rewarded { author, reward });
+	}
+
+	// Mints an uncle reward to an author who already has a full list of immature rewards
+	note_uncle {
+		let now = reward_accounts::<T>(1);
+		<frame_system::Pallet<T>>::set_block_number(now);
+		Pallet::<T>::on_initialize(now);
+		let author: T::AccountId = account("miner", 0, 0);
+		let reward = T::UncleReward::get() * Pallet::<T>::subsidy(now, Pallet::<T>::minted());
+	}: {
+		Pallet::<T>::note_uncle(author.clone(), One::one());
+	}
+	verify {
+		assert_last_event::<T>(Event::UncleRewarded { author, reward });
+	}
+}
+
+impl_benchmark_test_suite!(Pallet, crate::mock::new_test_ext(), crate::mock::Test);
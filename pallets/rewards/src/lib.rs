@@ -0,0 +1,392 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! Block reward module.
+//!
+//! Gets the author of the block from `pallet_authorship`, which finds it in the PoW pre-runtime
+//! digest with [`FindPowAuthor`], and mints the block reward to the author's account when the
+//! block is finalized.
+//! The reward follows the emission schedule from `crain_primitives::emission`.
+//!
+//! Transaction fees and tips are credited to the same author with [`FeesToAuthor`],
+//! minus an optional treasury share.
+//!
+//! Mined rewards are locked with a balance lock until they mature `RewardMaturity` blocks
+//! later, so that a reward lost in a reorg has not been spent already.
+//!
+//! Miners can include recent sibling blocks of the chain as uncles through the `set_uncles`
+//! inherent of `pallet_authorship`. [`VerifyPowSeal`] checks the seal of every uncle against the
+//! difficulty at its height and the uncle authors get a share of the block subsidy.
+
+pub use pallet::*;
+
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+pub mod weights;
+
+use crain_primitives::{
+	app,
+	emission::EmissionSchedule,
+	hash::HashAlgorithm,
+	seal::{hash_meets_difficulty, Compute, Seal},
+	Difficulty, ImmatureReward,
+};
+use frame_support::{
+	traits::{
+		ConstU32, Currency, FindAuthor, Get, Imbalance, LockIdentifier, LockableCurrency,
+		OnUnbalanced, WithdrawReasons,
+	},
+	weights::DispatchClass,
+	BoundedVec,
+};
+use frame_system::pallet_prelude::BlockNumberFor;
+use parity_scale_codec::Decode;
+use sp_consensus_pow::POW_ENGINE_ID;
+use sp_core::{sr25519, H256};
+use sp_runtime::{
+	traits::{Convert, Header as HeaderT, One, Saturating, Zero},
+	ConsensusEngineId, DigestItem, Perbill, SaturatedConversion,
+};
+use sp_std::marker::PhantomData;
+pub use weights::WeightInfo;
+
+/// Lock of the immature rewards.
+pub const REWARDS_LOCK_ID: LockIdentifier = *b"crn/rwds";
+
+/// Most accounts rewarded by a block: the author and the at most 10 uncles
+/// `pallet_authorship` accepts.
+pub type MaxRewardedPerBlock = ConstU32<11>;
+
+/// Most immature rewards of an account: one per block of the maturity period, the block reward
+/// and the uncle rewards of a block are kept together.
+pub struct MaxImmatureRewards<T>(PhantomData<T>);
+
+impl<T: Config> Get<u32> for MaxImmatureRewards<T> {
+	fn get() -> u32 {
+		T::RewardMaturity::get().saturated_into::<u32>().max(1)
+	}
+}
+
+pub type ImmatureRewardsOf<T> = BoundedVec<
+	ImmatureReward<<T as frame_system::Config>::BlockNumber, BalanceOf<T>>,
+	MaxImmatureRewards<T>,
+>;
+
+pub type BalanceOf<T> =
+	<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+pub type NegativeImbalanceOf<T> = <<T as Config>::Currency as Currency<
+	<T as frame_system::Config>::AccountId,
+>>::NegativeImbalance;
+
+/// Converts the author key to the account it is paid to: the account with the same sr25519 key.
+pub struct PublicToAccount;
+
+impl<AccountId: From<sr25519::Public>> Convert<app::Public, AccountId> for PublicToAccount {
+	fn convert(author: app::Public) -> AccountId {
+		sr25519::Public::from(author).into()
+	}
+}
+
+/// Finds the author key in the PoW pre-runtime digest and converts it to an account with `C`.
+pub struct FindPowAuthor<C>(PhantomData<C>);
+
+impl<AccountId, C: Convert<app::Public, AccountId>> FindAuthor<AccountId> for FindPowAuthor<C> {
+	fn find_author<'a, I>(digests: I) -> Option<AccountId>
+	where
+		I: 'a + IntoIterator<Item = (ConsensusEngineId, &'a [u8])>,
+	{
+		digests.into_iter().find_map(|(engine_id, mut data)| {
+			if engine_id == POW_ENGINE_ID {
+				app::Public::decode(&mut data).ok().map(C::convert)
+			} else {
+				None
+			}
+		})
+	}
+}
+
+#[frame_support::pallet]
+pub mod pallet {
+	use super::*;
+	use frame_support::pallet_prelude::*;
+	use frame_system::pallet_prelude::*;
+
+	#[pallet::config]
+	pub trait Config: frame_system::Config + pallet_authorship::Config {
+		type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
+		/// Currency the rewards are minted in.
+		type Currency: LockableCurrency<Self::AccountId, Moment = Self::BlockNumber>;
+		/// Number of blocks a mined reward stays locked for.
+		#[pallet::constant]
+		type RewardMaturity: Get<Self::BlockNumber>;
+		/// Emission schedule the block rewards follow.
+		type Emission: Get<EmissionSchedule>;
+		/// Part of the transaction fees and tips that goes to the treasury instead of the author.
+		#[pallet::constant]
+		type TreasuryShare: Get<Perbill>;
+		/// Handler of the treasury share of the fees. `()` burns it.
+		type Treasury: OnUnbalanced<NegativeImbalanceOf<Self>>;
+		/// Difficulty of the current block.
+		type CurrentDifficulty: Get<Difficulty>;
+		/// PoW algorithm the seals of the uncles are checked with.
+		type PowAlgorithm: Get<HashAlgorithm>;
+		/// Part of the block subsidy paid to the author of an uncle.
+		#[pallet::constant]
+		type UncleReward: Get<Perbill>;
+		/// Weight information for the hooks of this pallet.
+		type WeightInfo: WeightInfo;
+	}
+
+	#[pallet::pallet]
+	pub struct Pallet<T>(_);
+
+	/// Author of the current block still to be rewarded. Noted by `pallet_authorship`
+	/// and removed in `on_finalize`.
+	#[pallet::storage]
+	#[pallet::getter(fn author)]
+	pub type Author<T: Config> = StorageValue<_, T::AccountId>;
+
+	/// Total amount minted by the block rewards so far.
+	#[pallet::storage]
+	#[pallet::getter(fn minted)]
+	pub type Minted<T: Config> = StorageValue<_, BalanceOf<T>, ValueQuery>;
+
+	/// Difficulties of the blocks recent enough to have uncles.
+	#[pallet::storage]
+	#[pallet::getter(fn difficulty_at)]
+	pub type Difficulties<T: Config> = StorageMap<_, Twox64Concat, T::BlockNumber, Difficulty>;
+
+	/// Rewards of every account that are not mature yet, from the oldest.
+	#[pallet::storage]
+	#[pallet::getter(fn immature_rewards)]
+	pub type ImmatureRewards<T: Config> =
+		StorageMap<_, Blake2_128Concat, T::AccountId, ImmatureRewardsOf<T>, ValueQuery>;
+
+	/// Accounts with rewards maturing at a block.
+	#[pallet::storage]
+	pub type Maturing<T: Config> = StorageMap<
+		_,
+		Twox64Concat,
+		T::BlockNumber,
+		BoundedVec<T::AccountId, MaxRewardedPerBlock>,
+		ValueQuery,
+	>;
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// The author of the block was rewarded.
+		Rewarded { author: T::AccountId, reward: BalanceOf<T> },
+		/// The author of an uncle of the block was rewarded.
+		UncleRewarded { author: T::AccountId, reward: BalanceOf<T> },
+	}
+
+	#[pallet::hooks]
+	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+		fn on_initialize(now: BlockNumberFor<T>) -> Weight {
+			// Unlock the rewards maturing now
+			let matured = <Maturing<T>>::take(now);
+			for account in &matured {
+				<ImmatureRewards<T>>::mutate(account, |rewards| {
+					rewards.retain(|reward| reward.mature_at > now)
+				});
+				Self::update_lock(account);
+			}
+
+			// Uncles of the next blocks are checked against the difficulty of this one
+			<Difficulties<T>>::insert(now, T::CurrentDifficulty::get());
+			let generations = <T as pallet_authorship::Config>::UncleGenerations::get();
+			if now > generations {
+				<Difficulties<T>>::remove(now - generations - One::one());
+			}
+
+			// The block reward is minted in `on_finalize`
+			T::WeightInfo::on_initialize(matured.len() as u32)
+				.saturating_add(T::WeightInfo::on_finalize())
+		}
+
+		fn on_finalize(now: BlockNumberFor<T>) {
+			if let Some(author) = <Author<T>>::take() {
+				let minted = Self::minted();
+				let reward = Self::mint_locked(&author, Self::subsidy(now, minted));
+				<Minted<T>>::put(minted.saturating_add(reward));
+				Self::deposit_event(Event::Rewarded { author, reward });
+			}
+		}
+	}
+
+	#[pallet::call]
+	impl<T: Config> Pallet<T> {}
+}
+
+// Blocks without a valid author are never noted, so they are not rewarded
+impl<T: Config> pallet_authorship::EventHandler<T::AccountId, BlockNumberFor<T>> for Pallet<T> {
+	fn note_author(author: T::AccountId) {
+		<Author<T>>::put(author);
+	}
+
+	fn note_uncle(author: T::AccountId, _age: BlockNumberFor<T>) {
+		// `set_uncles` is a mandatory inherent without a weight, the block pays for the reward
+		<frame_system::Pallet<T>>::register_extra_weight_unchecked(
+			T::WeightInfo::note_uncle(),
+			DispatchClass::Mandatory,
+		);
+
+		let now = <frame_system::Pallet<T>>::block_number();
+		let minted = Self::minted();
+		let reward = Self::mint_locked(&author, T::UncleReward::get() * Self::subsidy(now, minted));
+		<Minted<T>>::put(minted.saturating_add(reward));
+		Self::deposit_event(Event::UncleRewarded { author, reward });
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	// Mint a mined reward and lock it until it matures. Returns the amount actually credited,
+	// which is zero if the reward can not create the account.
+	fn mint_locked(account: &T::AccountId, reward: BalanceOf<T>) -> BalanceOf<T> {
+		// Dropping the positive imbalance increases the total issuance
+		let credited = T::Currency::deposit_creating(account, reward).peek();
+
+		let maturity = T::RewardMaturity::get();
+		if maturity.is_zero() || credited.is_zero() {
+			return credited
+		}
+
+		let mature_at = <frame_system::Pallet<T>>::block_number() + maturity;
+		<ImmatureRewards<T>>::mutate(account, |rewards| match rewards.last_mut() {
+			Some(last) if last.mature_at == mature_at =>
+				last.amount = last.amount.saturating_add(credited),
+			_ => {
+				let reward = ImmatureReward { mature_at, amount: credited };
+				if let Err(reward) = rewards.try_push(reward) {
+					// Can not happen, an account has at most one reward per block of the
+					// maturity period. Keep the reward locked anyway, with the latest one.
+					if let Some(last) = rewards.last_mut() {
+						last.mature_at = mature_at;
+						last.amount = last.amount.saturating_add(reward.amount);
+					}
+				}
+			},
+		});
+		<Maturing<T>>::mutate(mature_at, |accounts| {
+			if !accounts.contains(account) {
+				// Can not fail, see `MaxRewardedPerBlock`
+				let pushed = accounts.try_push(account.clone());
+				debug_assert!(pushed.is_ok(), "More accounts rewarded than MaxRewardedPerBlock");
+			}
+		});
+		Self::update_lock(account);
+
+		credited
+	}
+
+	// Lock exactly the immature rewards of the account
+	fn update_lock(account: &T::AccountId) {
+		let locked = Self::immature_rewards(account)
+			.iter()
+			.fold(Zero::zero(), |locked: BalanceOf<T>, reward| locked.saturating_add(reward.amount));
+
+		if locked.is_zero() {
+			<ImmatureRewards<T>>::remove(account);
+			T::Currency::remove_lock(REWARDS_LOCK_ID, account);
+		} else {
+			T::Currency::set_lock(REWARDS_LOCK_ID, account, locked, WithdrawReasons::all());
+		}
+	}
+
+	/// Reward of the block at `height`, given the amount minted by the blocks before it.
+	pub fn subsidy(height: BlockNumberFor<T>, minted: BalanceOf<T>) -> BalanceOf<T> {
+		T::Emission::get()
+			.reward(height.saturated_into(), minted.saturated_into())
+			.saturated_into()
+	}
+
+	/// Subsidy of the block mined on top of the current one.
+	pub fn current_subsidy() -> BalanceOf<T> {
+		let height = <frame_system::Pallet<T>>::block_number() + 1u32.into();
+		Self::subsidy(height, Self::minted())
+	}
+
+	/// Subsidy of the block after the one mined on top of the current one,
+	/// assuming that one is rewarded.
+	pub fn next_subsidy() -> BalanceOf<T> {
+		let height = <frame_system::Pallet<T>>::block_number() + 1u32.into();
+		let minted = Self::minted().saturating_add(Self::subsidy(height, Self::minted()));
+		Self::subsidy(height + 1u32.into(), minted)
+	}
+}
+
+/// Handler of transaction fees for `pallet_transaction_payment::CurrencyAdapter`.
+/// Credits the fees and tips to the author of the current block, after the treasury share.
+/// Fees of blocks without an author are burnt.
+pub struct FeesToAuthor<T>(PhantomData<T>);
+
+impl<T: Config> OnUnbalanced<NegativeImbalanceOf<T>> for FeesToAuthor<T> {
+	fn on_unbalanceds<B>(mut fees_then_tips: impl Iterator<Item = NegativeImbalanceOf<T>>) {
+		if let Some(mut fees) = fees_then_tips.next() {
+			if let Some(tips) = fees_then_tips.next() {
+				tips.merge_into(&mut fees);
+			}
+
+			let treasury_share = T::TreasuryShare::get() * fees.peek();
+			let (to_treasury, to_author) = fees.split(treasury_share);
+			T::Treasury::on_unbalanced(to_treasury);
+
+			if let Some(author) = <pallet_authorship::Pallet<T>>::author() {
+				T::Currency::resolve_creating(&author, to_author);
+			}
+		}
+	}
+}
+
+/// Checks the PoW seal of an uncle for `pallet_authorship::SealVerify`: the work meets the
+/// difficulty at the uncle's height and the seal is signed by the uncle's author.
+pub struct VerifyPowSeal<T>(PhantomData<T>);
+
+impl<T> pallet_authorship::VerifySeal<T::Header, T::AccountId> for VerifyPowSeal<T>
+where
+	T: Config + frame_system::Config<Hash = H256>,
+{
+	fn verify_seal(header: &T::Header) -> Result<Option<T::AccountId>, &'static str> {
+		let mut header = header.clone();
+		let seal = match header.digest_mut().pop() {
+			Some(DigestItem::Seal(engine_id, seal)) if engine_id == POW_ENGINE_ID => seal,
+			_ => return Err("Uncle without a PoW seal"),
+		};
+		let seal = Seal::decode(&mut &seal[..]).map_err(|_| "Invalid PoW seal of an uncle")?;
+
+		let difficulty =
+			<Pallet<T>>::difficulty_at(header.number()).ok_or("Unknown difficulty of an uncle")?;
+		if seal.difficulty != difficulty || !hash_meets_difficulty(&seal.work, difficulty) {
+			return Err("Uncle does not meet the difficulty")
+		}
+
+		// The seal is made for the header without the seal, like the node does
+		let pre_hash = header.hash();
+		let compute = Compute { difficulty, pre_hash, nonce: seal.nonce };
+		// Memory-hard datasets are too big for the runtime, such chains have no uncles
+		let hasher = T::PowAlgorithm::get()
+			.plain_hasher()
+			.ok_or("Uncles are not supported by the memory-hard algorithm")?;
+		if compute.compute(hasher) != seal.solution() {
+			return Err("Invalid PoW work of an uncle")
+		}
+
+		let pre_digest = header
+			.digest()
+			.logs()
+			.iter()
+			.filter_map(DigestItem::as_pre_runtime)
+			.find(|(engine_id, _)| *engine_id == POW_ENGINE_ID)
+			.map(|(_, data)| data);
+		if !seal.is_signed_by(&pre_hash, pre_digest) {
+			return Err("Uncle is not signed by its author")
+		}
+
+		let digests = header.digest().logs().iter().filter_map(DigestItem::as_pre_runtime);
+		Ok(<T as pallet_authorship::Config>::FindAuthor::find_author(digests))
+	}
+}
@@ -0,0 +1,226 @@
+use crate as pallet_rewards;
+use crain_primitives::{
+	app,
+	emission::EmissionSchedule,
+	hash::{HashAlgorithm, Sha3Hasher},
+	seal::{hash_meets_difficulty, Compute},
+	Difficulty,
+};
+use frame_support::{
+	parameter_types,
+	traits::{ConstU32, ConstU64, ConstU8, Currency, Hooks, OnUnbalanced},
+	weights::IdentityFee,
+};
+use pallet_transaction_payment::CurrencyAdapter;
+use parity_scale_codec::Encode;
+use sp_consensus_pow::POW_ENGINE_ID;
+use sp_core::{sr25519, Pair, H256};
+use sp_runtime::{
+	testing::Header,
+	traits::{BlakeTwo256, Header as _, IdentityLookup},
+	AccountId32, Digest, DigestItem, Perbill,
+};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+pub type AccountId = AccountId32;
+pub type Balance = u64;
+
+pub const REWARD: Balance = 50;
+pub const HALVING_INTERVAL: u64 = 10;
+pub const SUPPLY_CAP: u128 = 1000;
+pub const UNCLE_GENERATIONS: u64 = 3;
+pub const DIFFICULTY: u64 = 10;
+
+frame_support::construct_runtime!(
+	pub enum Test where
+		Block = Block,
+		NodeBlock = Block,
+		UncheckedExtrinsic = UncheckedExtrinsic,
+	{
+		System: frame_system,
+		Balances: pallet_balances,
+		TransactionPayment: pallet_transaction_payment,
+		Authorship: pallet_authorship,
+		Rewards: pallet_rewards,
+	}
+);
+
+impl frame_system::Config for Test {
+	type BaseCallFilter = frame_support::traits::Everything;
+	type BlockWeights = ();
+	type BlockLength = ();
+	type DbWeight = ();
+	type Origin = Origin;
+	type Call = Call;
+	type Index = u64;
+	type BlockNumber = u64;
+	type Hash = H256;
+	type Hashing = BlakeTwo256;
+	type AccountId = AccountId;
+	type Lookup = IdentityLookup<Self::AccountId>;
+	type Header = Header;
+	type Event = Event;
+	type BlockHashCount = ConstU64<250>;
+	type Version = ();
+	type PalletInfo = PalletInfo;
+	type AccountData = pallet_balances::AccountData<Balance>;
+	type OnNewAccount = ();
+	type OnKilledAccount = ();
+	type SystemWeightInfo = ();
+	type SS58Prefix = ();
+	type OnSetCode = ();
+	type MaxConsumers = ConstU32<16>;
+}
+
+impl pallet_balances::Config for Test {
+	type MaxLocks = ConstU32<50>;
+	type MaxReserves = ();
+	type ReserveIdentifier = [u8; 8];
+	type Balance = Balance;
+	type Event = Event;
+	type DustRemoval = ();
+	type ExistentialDeposit = ExistentialDeposit;
+	type AccountStore = System;
+	type WeightInfo = ();
+}
+
+impl pallet_authorship::Config for Test {
+	type FindAuthor = pallet_rewards::FindPowAuthor<pallet_rewards::PublicToAccount>;
+	type UncleGenerations = ConstU64<UNCLE_GENERATIONS>;
+	type FilterUncle =
+		pallet_authorship::OnePerAuthorPerHeight<pallet_rewards::VerifyPowSeal<Test>, u64>;
+	type EventHandler = Rewards;
+}
+
+impl pallet_transaction_payment::Config for Test {
+	type OnChargeTransaction = CurrencyAdapter<Balances, pallet_rewards::FeesToAuthor<Test>>;
+	type OperationalFeeMultiplier = ConstU8<5>;
+	type WeightToFee = IdentityFee<Balance>;
+	type LengthToFee = IdentityFee<Balance>;
+	type FeeMultiplierUpdate = ();
+}
+
+/// Pays the treasury share of the fees to the account returned by [`treasury`].
+pub struct ToTreasury;
+
+impl OnUnbalanced<pallet_balances::NegativeImbalance<Test>> for ToTreasury {
+	fn on_nonzero_unbalanced(amount: pallet_balances::NegativeImbalance<Test>) {
+		Balances::resolve_creating(&treasury(), amount);
+	}
+}
+
+parameter_types! {
+	pub static ExistentialDeposit: Balance = 1;
+	pub static TreasuryShare: Perbill = Perbill::zero();
+	pub static CurrentDifficulty: Difficulty = Difficulty::from(DIFFICULTY);
+	pub static PowAlgorithm: HashAlgorithm = HashAlgorithm::Sha3;
+	pub static UncleReward: Perbill = Perbill::from_percent(50);
+	pub static RewardMaturity: u64 = 0;
+	pub static Emission: EmissionSchedule = EmissionSchedule {
+		initial_reward: REWARD as u128,
+		halving_interval: HALVING_INTERVAL,
+		supply_cap: SUPPLY_CAP,
+		tail_reward: None,
+	};
+}
+
+impl pallet_rewards::Config for Test {
+	type Event = Event;
+	type Currency = Balances;
+	type RewardMaturity = RewardMaturity;
+	type Emission = Emission;
+	type TreasuryShare = TreasuryShare;
+	type Treasury = ToTreasury;
+	type CurrentDifficulty = CurrentDifficulty;
+	type PowAlgorithm = PowAlgorithm;
+	type UncleReward = UncleReward;
+	type WeightInfo = ();
+}
+
+/// Author key with every byte set to `byte`.
+pub fn author(byte: u8) -> app::Public {
+	sr25519::Public::from_raw([byte; 32]).into()
+}
+
+/// Account the author key with every byte set to `byte` is paid to.
+pub fn account(byte: u8) -> AccountId {
+	AccountId32::new([byte; 32])
+}
+
+pub fn treasury() -> AccountId {
+	AccountId32::new([0xff; 32])
+}
+
+/// Signing key of a miner.
+pub fn miner(seed: &str) -> app::Pair {
+	app::Pair::from_string(&format!("//{}", seed), None).expect("Static seed is valid; qed")
+}
+
+/// Header of a block at `number` on top of the default parent hash, sealed by `signer`
+/// with a seal that meets `difficulty`. `extra` tells apart different blocks at the same height.
+pub fn sealed_header(number: u64, extra: u8, signer: &app::Pair, difficulty: u64) -> Header {
+	let mut header = Header::new(
+		number,
+		H256::repeat_byte(extra),
+		Default::default(),
+		Default::default(),
+		pow_digest(signer.public().encode()),
+	);
+
+	let difficulty = Difficulty::from(difficulty);
+	let pre_hash = header.hash();
+	let solution = (0u64..)
+		.map(|nonce| Compute { difficulty, pre_hash, nonce: nonce.into() }.compute(&Sha3Hasher))
+		.find(|solution| hash_meets_difficulty(&solution.work, difficulty))
+		.expect("A seal is found quickly for a tiny difficulty; qed");
+	let signature = signer.sign(&solution.signing_payload(&pre_hash));
+	header
+		.digest_mut()
+		.push(DigestItem::Seal(POW_ENGINE_ID, solution.into_seal(signature).encode()));
+	header
+}
+
+pub fn pow_digest(data: Vec<u8>) -> Digest {
+	Digest { logs: vec![DigestItem::PreRuntime(POW_ENGINE_ID, data)] }
+}
+
+/// Start a block with the given digest.
+pub fn start_block(number: u64, digest: Digest) {
+	System::reset_events();
+	System::initialize(&number, &Default::default(), &digest);
+	Authorship::on_initialize(number);
+	Rewards::on_initialize(number);
+}
+
+/// Finish the block started with [`start_block`].
+pub fn finish_block(number: u64) {
+	Authorship::on_finalize(number);
+	Rewards::on_finalize(number);
+}
+
+/// Run a whole block with the given digest.
+pub fn run_block(number: u64, digest: Digest) {
+	start_block(number, digest);
+	finish_block(number);
+}
+
+/// Run a whole block mined by the given author.
+pub fn mine_block(number: u64, author: app::Public) {
+	run_block(number, pow_digest(author.encode()));
+}
+
+/// Mine the blocks after the current one up to `number` by the given author.
+pub fn mine_to_block(number: u64, author: app::Public) {
+	while System::block_number() < number {
+		mine_block(System::block_number() + 1, author.clone());
+	}
+}
+
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let storage = frame_system::GenesisConfig::default().build_storage::<Test>().unwrap();
+	let mut ext = sp_io::TestExternalities::new(storage);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
@@ -0,0 +1,531 @@
+use crate::{
+	mock::*, Event as RewardsEvent, FindPowAuthor, Maturing, Minted, PublicToAccount,
+	VerifyPowSeal, WeightInfo,
+};
+use crain_primitives::{
+	emission::EmissionSchedule, hash::HashAlgorithm, seal::Seal, ImmatureReward,
+};
+use frame_support::{
+	assert_noop, assert_ok,
+	traits::{Currency, FindAuthor},
+	weights::{DispatchClass, DispatchInfo, PostDispatchInfo},
+};
+use pallet_authorship::{EventHandler, VerifySeal};
+use pallet_transaction_payment::ChargeTransactionPayment;
+use parity_scale_codec::{Decode, Encode};
+use sp_consensus_pow::POW_ENGINE_ID;
+use sp_core::Pair;
+use sp_runtime::{
+	traits::{Convert, Header as _, SignedExtension},
+	Digest, DigestItem, Perbill,
+};
+
+const PAYER_BALANCE: Balance = 1 << 50;
+
+// Charge a transaction signed by `who` the way the executive does. Returns the fee with the tip.
+fn pay_fee(who: &AccountId, tip: Balance) -> Balance {
+	let call = Call::System(frame_system::Call::remark { remark: vec![] });
+	let info = DispatchInfo { weight: 100, ..Default::default() };
+	let len = 10;
+
+	let pre = ChargeTransactionPayment::<Test>::from(tip)
+		.pre_dispatch(who, &call, &info, len)
+		.expect("The payer can afford the fee; qed");
+	assert_ok!(ChargeTransactionPayment::<Test>::post_dispatch(
+		Some(pre),
+		&info,
+		&PostDispatchInfo::default(),
+		len,
+		&Ok(()),
+	));
+
+	TransactionPayment::compute_fee(len as u32, &info, tip)
+}
+
+fn payer() -> AccountId {
+	let payer = account(9);
+	Balances::make_free_balance_be(&payer, PAYER_BALANCE);
+	payer
+}
+
+#[test]
+fn author_is_rewarded() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+
+		assert_eq!(Balances::free_balance(account(1)), REWARD);
+		assert_eq!(Balances::total_issuance(), REWARD);
+	});
+}
+
+#[test]
+fn reward_emits_event() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+
+		System::assert_last_event(
+			RewardsEvent::Rewarded { author: account(1), reward: REWARD }.into(),
+		);
+	});
+}
+
+#[test]
+fn rewards_add_up_over_blocks() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		mine_block(2, author(2));
+		mine_block(3, author(1));
+
+		assert_eq!(Balances::free_balance(account(1)), 2 * REWARD);
+		assert_eq!(Balances::free_balance(account(2)), REWARD);
+		assert_eq!(Balances::total_issuance(), 3 * REWARD);
+	});
+}
+
+#[test]
+fn author_is_cleared_after_block() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		assert_eq!(Rewards::author(), None);
+
+		// The author of the previous block is not paid again
+		run_block(2, Digest::default());
+		assert_eq!(Balances::free_balance(account(1)), REWARD);
+	});
+}
+
+#[test]
+fn block_without_author_is_not_rewarded() {
+	new_test_ext().execute_with(|| {
+		run_block(1, Digest::default());
+
+		assert_eq!(Balances::total_issuance(), 0);
+		assert!(System::events().is_empty());
+	});
+}
+
+#[test]
+fn invalid_author_is_not_rewarded() {
+	new_test_ext().execute_with(|| {
+		run_block(1, pow_digest(vec![1, 2, 3]));
+
+		assert_eq!(Balances::total_issuance(), 0);
+	});
+}
+
+#[test]
+fn digest_of_another_engine_is_ignored() {
+	new_test_ext().execute_with(|| {
+		let digest = Digest { logs: vec![DigestItem::PreRuntime(*b"aura", author(1).encode())] };
+		run_block(1, digest);
+
+		assert_eq!(Balances::free_balance(account(1)), 0);
+	});
+}
+
+#[test]
+fn reward_halves() {
+	new_test_ext().execute_with(|| {
+		mine_block(HALVING_INTERVAL, author(1));
+		mine_block(2 * HALVING_INTERVAL, author(2));
+
+		assert_eq!(Balances::free_balance(account(1)), REWARD / 2);
+		assert_eq!(Balances::free_balance(account(2)), REWARD / 4);
+	});
+}
+
+#[test]
+fn minted_amount_is_tracked() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		run_block(2, Digest::default());
+		mine_block(3, author(1));
+
+		assert_eq!(Rewards::minted(), 2 * REWARD);
+	});
+}
+
+#[test]
+fn reward_stops_at_supply_cap() {
+	new_test_ext().execute_with(|| {
+		Minted::<Test>::put(SUPPLY_CAP as Balance - 20);
+
+		mine_block(1, author(1));
+		mine_block(2, author(2));
+
+		assert_eq!(Balances::free_balance(account(1)), 20);
+		assert_eq!(Balances::free_balance(account(2)), 0);
+		assert_eq!(Rewards::minted(), SUPPLY_CAP as Balance);
+	});
+}
+
+#[test]
+fn tail_emission_continues_past_cap() {
+	new_test_ext().execute_with(|| {
+		Emission::set(EmissionSchedule { tail_reward: Some(5), ..Emission::get() });
+		Minted::<Test>::put(SUPPLY_CAP as Balance);
+
+		mine_block(1, author(1));
+
+		assert_eq!(Balances::free_balance(account(1)), 5);
+	});
+}
+
+#[test]
+fn subsidies_for_wallets() {
+	new_test_ext().execute_with(|| {
+		System::set_block_number(HALVING_INTERVAL - 2);
+		assert_eq!(Rewards::current_subsidy(), REWARD);
+		assert_eq!(Rewards::next_subsidy(), REWARD / 2);
+
+		Minted::<Test>::put(SUPPLY_CAP as Balance - 10);
+		assert_eq!(Rewards::current_subsidy(), 10);
+		assert_eq!(Rewards::next_subsidy(), 0);
+	});
+}
+
+#[test]
+fn fees_go_to_author() {
+	new_test_ext().execute_with(|| {
+		let payer = payer();
+		start_block(1, pow_digest(author(1).encode()));
+		let fee = pay_fee(&payer, 0) + pay_fee(&payer, 0);
+		finish_block(1);
+
+		assert!(fee > 0);
+		assert_eq!(Balances::free_balance(&payer), PAYER_BALANCE - fee);
+		assert_eq!(Balances::free_balance(account(1)), REWARD + fee);
+	});
+}
+
+#[test]
+fn tips_go_to_author() {
+	new_test_ext().execute_with(|| {
+		let payer = payer();
+		start_block(1, pow_digest(author(1).encode()));
+		let fee = pay_fee(&payer, 0);
+		let fee_with_tip = pay_fee(&payer, 1_000);
+		finish_block(1);
+
+		assert_eq!(fee_with_tip, fee + 1_000);
+		assert_eq!(Balances::free_balance(account(1)), REWARD + fee + fee_with_tip);
+	});
+}
+
+#[test]
+fn treasury_takes_its_share_of_fees() {
+	new_test_ext().execute_with(|| {
+		TreasuryShare::set(Perbill::from_percent(20));
+		let payer = payer();
+		start_block(1, pow_digest(author(1).encode()));
+		let fee = pay_fee(&payer, 1_000);
+		finish_block(1);
+
+		let treasury_share = Perbill::from_percent(20) * fee;
+		assert_eq!(Balances::free_balance(treasury()), treasury_share);
+		assert_eq!(Balances::free_balance(account(1)), REWARD + fee - treasury_share);
+	});
+}
+
+#[test]
+fn fees_of_block_without_author_are_burnt() {
+	new_test_ext().execute_with(|| {
+		let payer = payer();
+		start_block(1, Digest::default());
+		let fee = pay_fee(&payer, 0);
+		finish_block(1);
+
+		assert_eq!(Balances::total_issuance(), PAYER_BALANCE - fee);
+	});
+}
+
+type PowAuthor = FindPowAuthor<PublicToAccount>;
+
+#[test]
+fn finds_author_in_pow_digest() {
+	let data = author(1).encode();
+	let digests = vec![(*b"aura", &[0u8; 32][..]), (POW_ENGINE_ID, &data[..])];
+
+	assert_eq!(<PowAuthor as FindAuthor<AccountId>>::find_author(digests), Some(account(1)));
+}
+
+#[test]
+fn finds_no_author_without_pow_digest() {
+	let data = author(1).encode();
+
+	assert_eq!(
+		<PowAuthor as FindAuthor<AccountId>>::find_author(vec![(*b"aura", &data[..])]),
+		None
+	);
+	assert_eq!(
+		<PowAuthor as FindAuthor<AccountId>>::find_author(vec![(POW_ENGINE_ID, &[1u8, 2][..])]),
+		None
+	);
+}
+
+#[test]
+fn author_is_known_during_block() {
+	new_test_ext().execute_with(|| {
+		start_block(1, pow_digest(author(1).encode()));
+		assert_eq!(Authorship::author(), Some(account(1)));
+		finish_block(1);
+	});
+}
+
+fn miner_account(seed: &str) -> AccountId {
+	PublicToAccount::convert(miner(seed).public())
+}
+
+#[test]
+fn uncle_seal_is_verified() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		let uncle = sealed_header(1, 1, &miner("Bob"), DIFFICULTY);
+
+		assert_eq!(VerifyPowSeal::<Test>::verify_seal(&uncle), Ok(Some(miner_account("Bob"))));
+	});
+}
+
+#[test]
+fn uncle_with_another_difficulty_is_rejected() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		let uncle = sealed_header(1, 1, &miner("Bob"), DIFFICULTY / 2);
+
+		assert!(VerifyPowSeal::<Test>::verify_seal(&uncle).is_err());
+	});
+}
+
+#[test]
+fn uncle_is_rejected_with_memory_hard_algorithm() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		let uncle = sealed_header(1, 1, &miner("Bob"), DIFFICULTY);
+		PowAlgorithm::set(HashAlgorithm::MemoryHard);
+
+		assert_eq!(
+			VerifyPowSeal::<Test>::verify_seal(&uncle),
+			Err("Uncles are not supported by the memory-hard algorithm"),
+		);
+	});
+}
+
+#[test]
+fn uncle_without_seal_is_rejected() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		let mut uncle = sealed_header(1, 1, &miner("Bob"), DIFFICULTY);
+		uncle.digest_mut().pop();
+
+		assert!(VerifyPowSeal::<Test>::verify_seal(&uncle).is_err());
+	});
+}
+
+#[test]
+fn uncle_signed_by_another_miner_is_rejected() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		let mut uncle = sealed_header(1, 1, &miner("Bob"), DIFFICULTY);
+
+		// Keep the work of Bob but sign it by Mallory
+		let seal = match uncle.digest_mut().pop() {
+			Some(DigestItem::Seal(_, seal)) => Seal::decode(&mut &seal[..]).unwrap(),
+			_ => unreachable!("The header is sealed; qed"),
+		};
+		let pre_hash = uncle.hash();
+		let signature = miner("Mallory").sign(&seal.solution().signing_payload(&pre_hash));
+		let forged = seal.solution().into_seal(signature);
+		uncle.digest_mut().push(DigestItem::Seal(POW_ENGINE_ID, forged.encode()));
+
+		assert!(VerifyPowSeal::<Test>::verify_seal(&uncle).is_err());
+	});
+}
+
+#[test]
+fn too_old_uncle_is_rejected() {
+	new_test_ext().execute_with(|| {
+		for number in 1..=UNCLE_GENERATIONS + 2 {
+			mine_block(number, author(1));
+		}
+		let uncle = sealed_header(1, 1, &miner("Bob"), DIFFICULTY);
+
+		assert_eq!(Rewards::difficulty_at(1), None);
+		assert!(VerifyPowSeal::<Test>::verify_seal(&uncle).is_err());
+	});
+}
+
+#[test]
+fn uncle_author_is_rewarded() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		let uncle = sealed_header(1, 1, &miner("Bob"), DIFFICULTY);
+
+		start_block(2, pow_digest(author(2).encode()));
+		assert_ok!(Authorship::set_uncles(Origin::none(), vec![uncle]));
+		System::assert_has_event(
+			RewardsEvent::UncleRewarded { author: miner_account("Bob"), reward: REWARD / 2 }.into(),
+		);
+		finish_block(2);
+
+		assert_eq!(Balances::free_balance(miner_account("Bob")), REWARD / 2);
+		assert_eq!(Balances::free_balance(account(2)), REWARD);
+		assert_eq!(Rewards::minted(), 2 * REWARD + REWARD / 2);
+	});
+}
+
+#[test]
+fn uncle_reward_weight_is_registered() {
+	new_test_ext().execute_with(|| {
+		let before = *System::block_weight().get(DispatchClass::Mandatory);
+		Rewards::note_uncle(account(2), 1);
+
+		assert_eq!(
+			*System::block_weight().get(DispatchClass::Mandatory),
+			before + <() as WeightInfo>::note_uncle(),
+		);
+	});
+}
+
+#[test]
+fn invalid_uncle_is_not_included() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+		let uncle = sealed_header(1, 1, &miner("Bob"), DIFFICULTY / 2);
+
+		start_block(2, pow_digest(author(2).encode()));
+		assert_noop!(
+			Authorship::set_uncles(Origin::none(), vec![uncle]),
+			"Uncle does not meet the difficulty"
+		);
+	});
+}
+
+#[test]
+fn reward_is_locked_until_maturity() {
+	new_test_ext().execute_with(|| {
+		RewardMaturity::set(5);
+		mine_block(1, author(1));
+
+		assert_eq!(Balances::free_balance(account(1)), REWARD);
+		assert_eq!(Balances::usable_balance(account(1)), 0);
+		assert_eq!(
+			Rewards::immature_rewards(account(1)),
+			vec![ImmatureReward { mature_at: 6, amount: REWARD }]
+		);
+
+		mine_to_block(5, author(2));
+		assert_eq!(Balances::usable_balance(account(1)), 0);
+
+		mine_block(6, author(2));
+		assert_eq!(Balances::usable_balance(account(1)), REWARD);
+		assert!(Rewards::immature_rewards(account(1)).is_empty());
+		assert!(Balances::locks(account(1)).is_empty());
+	});
+}
+
+#[test]
+fn rewards_mature_one_by_one() {
+	new_test_ext().execute_with(|| {
+		RewardMaturity::set(5);
+		mine_block(1, author(1));
+		mine_to_block(3, author(1));
+		assert_eq!(Rewards::immature_rewards(account(1)).len(), 3);
+		assert_eq!(Balances::usable_balance(account(1)), 0);
+
+		mine_to_block(6, author(2));
+		assert_eq!(
+			Rewards::immature_rewards(account(1)),
+			vec![
+				ImmatureReward { mature_at: 7, amount: REWARD },
+				ImmatureReward { mature_at: 8, amount: REWARD },
+			]
+		);
+		assert_eq!(Balances::usable_balance(account(1)), REWARD);
+
+		mine_to_block(8, author(2));
+		assert_eq!(Balances::usable_balance(account(1)), 3 * REWARD);
+		assert!(!Maturing::<Test>::contains_key(8));
+	});
+}
+
+#[test]
+fn uncle_reward_is_locked() {
+	new_test_ext().execute_with(|| {
+		RewardMaturity::set(5);
+		mine_block(1, author(1));
+		let uncle = sealed_header(1, 1, &miner("Bob"), DIFFICULTY);
+
+		start_block(2, pow_digest(author(2).encode()));
+		assert_ok!(Authorship::set_uncles(Origin::none(), vec![uncle]));
+		finish_block(2);
+
+		assert_eq!(Balances::usable_balance(miner_account("Bob")), 0);
+		assert_eq!(
+			Rewards::immature_rewards(miner_account("Bob")),
+			vec![ImmatureReward { mature_at: 7, amount: REWARD / 2 }]
+		);
+
+		mine_to_block(7, author(2));
+		assert_eq!(Balances::usable_balance(miner_account("Bob")), REWARD / 2);
+	});
+}
+
+#[test]
+fn zero_maturity_does_not_lock() {
+	new_test_ext().execute_with(|| {
+		mine_block(1, author(1));
+
+		assert_eq!(Balances::usable_balance(account(1)), REWARD);
+		assert!(Rewards::immature_rewards(account(1)).is_empty());
+		assert!(Balances::locks(account(1)).is_empty());
+	});
+}
+
+#[test]
+fn reward_below_existential_deposit_is_not_counted() {
+	new_test_ext().execute_with(|| {
+		ExistentialDeposit::set(REWARD + 1);
+		RewardMaturity::set(5);
+		mine_block(1, author(1));
+
+		assert_eq!(Balances::free_balance(account(1)), 0);
+		assert_eq!(Rewards::minted(), 0);
+		assert!(Rewards::immature_rewards(account(1)).is_empty());
+		assert!(!Maturing::<Test>::contains_key(6));
+		System::assert_has_event(RewardsEvent::Rewarded { author: account(1), reward: 0 }.into());
+	});
+}
+
+#[test]
+fn rewards_of_the_same_block_are_kept_together() {
+	new_test_ext().execute_with(|| {
+		RewardMaturity::set(5);
+		mine_block(1, author(1));
+		let bob = miner("Bob");
+		let uncle = sealed_header(1, 1, &bob, DIFFICULTY);
+
+		start_block(2, pow_digest(bob.public().encode()));
+		assert_ok!(Authorship::set_uncles(Origin::none(), vec![uncle]));
+		finish_block(2);
+
+		assert_eq!(
+			Rewards::immature_rewards(miner_account("Bob")),
+			vec![ImmatureReward { mature_at: 7, amount: REWARD + REWARD / 2 }]
+		);
+		assert_eq!(Maturing::<Test>::get(7).len(), 1);
+	});
+}
+
+#[test]
+fn immature_rewards_are_bounded_by_the_maturity() {
+	new_test_ext().execute_with(|| {
+		RewardMaturity::set(3);
+		mine_block(1, author(1));
+		mine_block(2, author(1));
+		for block in 3..10 {
+			mine_block(block, author(1));
+			assert_eq!(Rewards::immature_rewards(account(1)).len(), 3);
+		}
+		assert_eq!(Balances::usable_balance(account(1)), 6 * REWARD);
+	});
+}
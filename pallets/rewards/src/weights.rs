@@ -0,0 +1,117 @@
+//! Weights for pallet_rewards
+//!
+//! Not generated yet: the storage accesses follow the benchmarks in `benchmarking.rs`, but the
+//! execution times are estimates. Replace this file with the output of:
+//!
+//! ./target/release/crain-node benchmark pallet --chain dev --execution wasm
+//! --wasm-execution compiled --pallet pallet_rewards --extrinsic '*' --steps 50 --repeat 20
+//! --output ./pallets/rewards/src/weights.rs
+
+#![allow(unused_parens)]
+#![allow(unused_imports)]
+
+use frame_support::{
+	traits::Get,
+	weights::{constants::RocksDbWeight, Weight},
+};
+use sp_std::marker::PhantomData;
+
+/// Weight functions needed for pallet_rewards.
+pub trait WeightInfo {
+	fn on_initialize(m: u32) -> Weight;
+	fn on_finalize() -> Weight;
+	fn note_uncle() -> Weight;
+}
+
+/// Weights for pallet_rewards using the Substrate node and recommended hardware.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	// Storage: Rewards Maturing (r:1 w:1)
+	// Storage: Difficulty CurrentDifficulty (r:1 w:0)
+	// Storage: Rewards Difficulties (r:0 w:2)
+	// Storage: Rewards ImmatureRewards (r:1 w:1)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: System Account (r:1 w:1)
+	fn on_initialize(m: u32) -> Weight {
+		(10_000_000 as Weight)
+			.saturating_add((25_000_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(T::DbWeight::get().reads(2 as Weight))
+			.saturating_add(T::DbWeight::get().reads((3 as Weight).saturating_mul(m as Weight)))
+			.saturating_add(T::DbWeight::get().writes(3 as Weight))
+			.saturating_add(T::DbWeight::get().writes((3 as Weight).saturating_mul(m as Weight)))
+	}
+	// Storage: Rewards Author (r:1 w:1)
+	// Storage: Rewards Minted (r:1 w:1)
+	// Storage: System Account (r:1 w:1)
+	// Storage: Balances TotalIssuance (r:1 w:1)
+	// Storage: Rewards ImmatureRewards (r:1 w:1)
+	// Storage: Rewards Maturing (r:1 w:1)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: System EventCount (r:1 w:1)
+	// Storage: System Events (r:1 w:1)
+	fn on_finalize() -> Weight {
+		(40_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(9 as Weight))
+			.saturating_add(T::DbWeight::get().writes(9 as Weight))
+	}
+	// Storage: System BlockWeight (r:1 w:1)
+	// Storage: Rewards Minted (r:1 w:1)
+	// Storage: System Account (r:1 w:1)
+	// Storage: Balances TotalIssuance (r:1 w:1)
+	// Storage: Rewards ImmatureRewards (r:1 w:1)
+	// Storage: Rewards Maturing (r:1 w:1)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: System EventCount (r:1 w:1)
+	// Storage: System Events (r:1 w:1)
+	fn note_uncle() -> Weight {
+		(40_000_000 as Weight)
+			.saturating_add(T::DbWeight::get().reads(9 as Weight))
+			.saturating_add(T::DbWeight::get().writes(9 as Weight))
+	}
+}
+
+// For backwards compatibility and tests
+impl WeightInfo for () {
+	// Storage: Rewards Maturing (r:1 w:1)
+	// Storage: Difficulty CurrentDifficulty (r:1 w:0)
+	// Storage: Rewards Difficulties (r:0 w:2)
+	// Storage: Rewards ImmatureRewards (r:1 w:1)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: System Account (r:1 w:1)
+	fn on_initialize(m: u32) -> Weight {
+		(10_000_000 as Weight)
+			.saturating_add((25_000_000 as Weight).saturating_mul(m as Weight))
+			.saturating_add(RocksDbWeight::get().reads(2 as Weight))
+			.saturating_add(RocksDbWeight::get().reads((3 as Weight).saturating_mul(m as Weight)))
+			.saturating_add(RocksDbWeight::get().writes(3 as Weight))
+			.saturating_add(RocksDbWeight::get().writes((3 as Weight).saturating_mul(m as Weight)))
+	}
+	// Storage: Rewards Author (r:1 w:1)
+	// Storage: Rewards Minted (r:1 w:1)
+	// Storage: System Account (r:1 w:1)
+	// Storage: Balances TotalIssuance (r:1 w:1)
+	// Storage: Rewards ImmatureRewards (r:1 w:1)
+	// Storage: Rewards Maturing (r:1 w:1)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: System EventCount (r:1 w:1)
+	// Storage: System Events (r:1 w:1)
+	fn on_finalize() -> Weight {
+		(40_000_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(9 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(9 as Weight))
+	}
+	// Storage: System BlockWeight (r:1 w:1)
+	// Storage: Rewards Minted (r:1 w:1)
+	// Storage: System Account (r:1 w:1)
+	// Storage: Balances TotalIssuance (r:1 w:1)
+	// Storage: Rewards ImmatureRewards (r:1 w:1)
+	// Storage: Rewards Maturing (r:1 w:1)
+	// Storage: Balances Locks (r:1 w:1)
+	// Storage: System EventCount (r:1 w:1)
+	// Storage: System Events (r:1 w:1)
+	fn note_uncle() -> Weight {
+		(40_000_000 as Weight)
+			.saturating_add(RocksDbWeight::get().reads(9 as Weight))
+			.saturating_add(RocksDbWeight::get().writes(9 as Weight))
+	}
+}
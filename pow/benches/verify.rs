@@ -0,0 +1,48 @@
+//! Compares the cost of verifying a seal with the plain and the memory-hard hashers.
+//!
+//! Run with `cargo bench -p crain-pow`.
+
+use crain_pow::{
+	hash::{Blake2bHasher, KeccakHasher, Sha3Hasher},
+	memory_hard::Dataset,
+	Compute, PowHasher,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use sp_core::{H256, U256};
+
+fn compute() -> Compute {
+	Compute {
+		difficulty: U256::from(1_000_000),
+		pre_hash: H256::repeat_byte(7),
+		nonce: U256::from(42),
+	}
+}
+
+// Verifying a seal is computing a single hash and comparing it with the seal
+fn verify<H: PowHasher>(hasher: &H) -> bool {
+	let compute = compute();
+	let seal = compute.clone().compute(hasher);
+	compute.compute(hasher) == seal
+}
+
+fn bench_verify(c: &mut Criterion) {
+	let mut group = c.benchmark_group("verify");
+	group.bench_function("sha3", |b| b.iter(|| verify(&Sha3Hasher)));
+	group.bench_function("blake2b", |b| b.iter(|| verify(&Blake2bHasher)));
+	group.bench_function("keccak", |b| b.iter(|| verify(&KeccakHasher)));
+
+	let dataset = Dataset::new(H256::repeat_byte(1));
+	group.bench_function("memory-hard", |b| b.iter(|| verify(&dataset)));
+	group.finish();
+}
+
+// Building a dataset happens once per epoch, or once per node start
+fn bench_dataset(c: &mut Criterion) {
+	let mut group = c.benchmark_group("dataset");
+	group.sample_size(10);
+	group.bench_function("build", |b| b.iter(|| Dataset::new(H256::repeat_byte(1))));
+	group.finish();
+}
+
+criterion_group!(benches, bench_verify, bench_dataset);
+criterion_main!(benches);
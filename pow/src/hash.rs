@@ -0,0 +1,2 @@
+// The hashers live in the primitives so that the runtime can verify seals too
+pub use crain_primitives::hash::*;
@@ -1,86 +1,104 @@
-use parity_scale_codec::{Decode, Encode};
+use crain_primitives::MIN_DIFFICULTY;
+use parity_scale_codec::Decode;
 use sc_consensus_pow::{Error as PowError, PowAlgorithm};
-use sha3::{Digest, Sha3_256};
 use sp_api::ProvideRuntimeApi;
 use sc_client_api::{backend::AuxStore, blockchain::HeaderBackend};
+use sp_blockchain::HeaderMetadata;
 use sp_consensus_pow::{DifficultyApi, Seal as RawSeal};
 use sp_core::{blake2_256, H256, U256};
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::Block as BlockT;
 use std::sync::Arc;
 
-// Exported module of the whole app
-pub mod app {
-	use sp_application_crypto::{app_crypto, sr25519};
-	use sp_core::crypto::KeyTypeId;
+pub mod hash;
+pub mod memory_hard;
+pub mod metrics;
+pub mod miner;
+pub mod select_chain;
 
-	pub const ID: KeyTypeId = KeyTypeId(*b"crn1");
-	
-	app_crypto!(sr25519, ID);
-}
+pub use hash::{HashAlgorithm, PowHasher};
+pub use memory_hard::{Dataset, MemoryHardAlgorithm};
+pub use metrics::PowMetrics;
+pub use select_chain::HeaviestChain;
 
+use miner::{JobHasher, MiningJob};
 
-// Check if the given hash satisfies the given difficulty
-// Multiply both together. If the product overflows the bounds of U256 then
-// the hash was to high
-pub fn hash_meets_difficulty(hash: &H256, difficulty: U256) -> bool {
-	let num_hash = U256::from(&hash[..]);
-	let (_, overflowed) = num_hash.overflowing_mul(difficulty);
+// Exported module of the whole app. Lives in the primitives so that the runtime can decode
+// the author too.
+pub use crain_primitives::app;
+// Seals are checked by the runtime too
+pub use crain_primitives::seal::{hash_meets_difficulty, Compute, Seal, Solution};
 
-	!overflowed
-}
 
+// Checks of a seal that need no hasher: the seal decodes, its difficulty is not below the
+// lowest one the runtime can retarget to, and its work meets that difficulty. Returns the
+// decoded seal if they pass. Whether the difficulty is the one of the chain is left to `verify`.
+pub fn preliminary_check_work(seal: &RawSeal) -> Option<Seal> {
+	let seal = Seal::decode(&mut &seal[..]).ok()?;
 
-// A Seal struct to encode as Vec<u8> and use as the 'RawSeal' type
-#[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
-pub struct Seal {
-	pub difficulty: U256,
-	pub work: H256,
-	pub nonce: U256
-}
+	// A zero difficulty is met by any work
+	if seal.difficulty < U256::from(MIN_DIFFICULTY.max(1)) {
+		return None
+	}
 
+	if !hash_meets_difficulty(&seal.work, seal.difficulty) {
+		return None
+	}
 
-// An attempt to solve a PoW
-#[derive(Clone, PartialEq, Eq, Encode, Decode, Debug)]
-pub struct Compute {
-	pub difficulty: U256,
-	pub pre_hash: H256,
-	pub nonce: U256
+	Some(seal)
 }
 
-impl Compute {
-	pub fn compute(self) -> Seal {
-		let work = H256::from_slice(Sha3_256::digest(&self.encode()[..]).as_slice());
+// Cheap checks of a seal that need neither the parent block nor the runtime:
+// the checks of `preliminary_check_work` and the work really is the hash of the pre_hash
+// and the nonce. The signature is left to the full verification, which knows the author.
+pub fn preliminary_check<H: PowHasher + ?Sized>(
+	hasher: &H,
+	pre_hash: &H256,
+	seal: &RawSeal,
+) -> bool {
+	let seal = match preliminary_check_work(seal) {
+		Some(seal) => seal,
+		None => return false,
+	};
 
-		Seal {
-			nonce: self.nonce,
-			difficulty: self.difficulty,
-			work,
-		}
-	}
+	let compute = Compute { difficulty: seal.difficulty, pre_hash: *pre_hash, nonce: seal.nonce };
+
+	compute.compute(hasher) == seal.solution()
 }
 
 
-// A simple Sha3 hashing algorithm 
-pub struct Sha3Algorithm<C> {
-	client: Arc<C>
+// PoW of a plain hash function: Sha3 by default, Blake2b or Keccak if the runtime says so
+// through `PowAlgorithmApi`
+pub struct HashPowAlgorithm<C> {
+	client: Arc<C>,
+	hasher: &'static (dyn PowHasher + Sync),
+	metrics: Option<PowMetrics>,
 }
 
-impl<C> Sha3Algorithm<C> {
-	pub fn new(client: Arc<C>) -> Self {
-		Self { client }
+impl<C> HashPowAlgorithm<C> {
+	pub fn new(client: Arc<C>, hasher: &'static (dyn PowHasher + Sync)) -> Self {
+		Self { client, hasher, metrics: None }
+	}
+
+	// Report import queue metrics to the given registry
+	pub fn with_metrics(
+		mut self,
+		registry: Option<&prometheus_endpoint::Registry>,
+	) -> Result<Self, prometheus_endpoint::PrometheusError> {
+		self.metrics = registry.map(PowMetrics::register).transpose()?;
+		Ok(self)
 	}
 }
 
-impl<C> Clone for Sha3Algorithm<C> {
+impl<C> Clone for HashPowAlgorithm<C> {
 	fn clone(&self) -> Self {
-		Self::new(self.client.clone())
+		Self { client: self.client.clone(), hasher: self.hasher, metrics: self.metrics.clone() }
 	}
 }
 
 
 // Implementing PowAlgorithm trait is a must 
-impl<B: BlockT<Hash = H256>, C> PowAlgorithm<B> for Sha3Algorithm<C> 
+impl<B: BlockT<Hash = H256>, C> PowAlgorithm<B> for HashPowAlgorithm<C> 
 	where
 		C: HeaderBackend<B> + AuxStore + ProvideRuntimeApi<B>,
 		C::Api: DifficultyApi<B, U256>,
@@ -114,7 +132,7 @@ impl<B: BlockT<Hash = H256>, C> PowAlgorithm<B> for Sha3Algorithm<C>
 			&self,
 			_parent: &BlockId<B>,
 			pre_hash: &H256,
-			_pre_digest: Option<&[u8]>,
+			pre_digest: Option<&[u8]>,
 			seal: &RawSeal,
 			difficulty: Self::Difficulty,
 		) -> Result<bool, PowError<B>> {
@@ -136,20 +154,259 @@ impl<B: BlockT<Hash = H256>, C> PowAlgorithm<B> for Sha3Algorithm<C>
 				nonce: seal.nonce,
 			};
 
-			if compute.compute() != seal {
+			if compute.compute(self.hasher) != seal.solution() {
 				return Ok(false);
 			}
 
-			Ok(true)
+			// Check that the block is sealed by its author
+			Ok(seal.is_signed_by(pre_hash, pre_digest))
 		}
 
-	fn preliminary_verify(
+		// Reject bad seals before the expensive verification (which calls the runtime) runs
+		fn preliminary_verify(
 			&self,
-			_pre_hash: &<B as BlockT>::Hash,
-			_seal: &RawSeal,
+			pre_hash: &<B as BlockT>::Hash,
+			seal: &RawSeal,
 		) -> Result<Option<bool>, PowError<B>> {
-			Ok(None)
+			let passed = preliminary_check(self.hasher, pre_hash, seal);
+
+			if let Some(metrics) = &self.metrics {
+				metrics.preliminary_checked.inc();
+				if !passed {
+					metrics.preliminary_rejected.inc();
+				}
+			}
+
+			// A seal passing the check still has to go through the full verification
+			Ok(if passed { None } else { Some(false) })
+		}
+
+	}
+
+impl<C: Send + Sync + 'static> JobHasher for HashPowAlgorithm<C> {
+	type Hasher = &'static (dyn PowHasher + Sync);
+
+	fn hasher(&self, _job: &MiningJob) -> Result<Self::Hasher, String> {
+		Ok(self.hasher)
+	}
+}
+
+// PoW algorithm of the chain, picked by the runtime's `PowAlgorithmApi`
+pub enum ChainPowAlgorithm<B, C> {
+	Hash(HashPowAlgorithm<C>),
+	MemoryHard(MemoryHardAlgorithm<B, C>),
+}
+
+impl<B, C> ChainPowAlgorithm<B, C> {
+	pub fn new(client: Arc<C>, algorithm: HashAlgorithm) -> Self {
+		match algorithm.plain_hasher() {
+			Some(hasher) => ChainPowAlgorithm::Hash(HashPowAlgorithm::new(client, hasher)),
+			None => ChainPowAlgorithm::MemoryHard(MemoryHardAlgorithm::new(client)),
+		}
+	}
+
+	// Report import queue metrics to the given registry
+	pub fn with_metrics(
+		self,
+		registry: Option<&prometheus_endpoint::Registry>,
+	) -> Result<Self, prometheus_endpoint::PrometheusError> {
+		Ok(match self {
+			ChainPowAlgorithm::Hash(algorithm) =>
+				ChainPowAlgorithm::Hash(algorithm.with_metrics(registry)?),
+			ChainPowAlgorithm::MemoryHard(algorithm) =>
+				ChainPowAlgorithm::MemoryHard(algorithm.with_metrics(registry)?),
+		})
+	}
+}
+
+impl<B, C> Clone for ChainPowAlgorithm<B, C> {
+	fn clone(&self) -> Self {
+		match self {
+			ChainPowAlgorithm::Hash(algorithm) => ChainPowAlgorithm::Hash(algorithm.clone()),
+			ChainPowAlgorithm::MemoryHard(algorithm) =>
+				ChainPowAlgorithm::MemoryHard(algorithm.clone()),
+		}
+	}
+}
+
+impl<B: BlockT<Hash = H256>, C> PowAlgorithm<B> for ChainPowAlgorithm<B, C>
+where
+	C: HeaderBackend<B>
+		+ HeaderMetadata<B, Error = sp_blockchain::Error>
+		+ AuxStore
+		+ ProvideRuntimeApi<B>,
+	C::Api: DifficultyApi<B, U256>,
+{
+	type Difficulty = U256;
+
+	fn difficulty(&self, parent: B::Hash) -> Result<U256, PowError<B>> {
+		match self {
+			ChainPowAlgorithm::Hash(algorithm) => PowAlgorithm::<B>::difficulty(algorithm, parent),
+			ChainPowAlgorithm::MemoryHard(algorithm) => algorithm.difficulty(parent),
+		}
+	}
+
+	fn break_tie(&self, own_seal: &RawSeal, new_seal: &RawSeal) -> bool {
+		match self {
+			ChainPowAlgorithm::Hash(algorithm) =>
+				PowAlgorithm::<B>::break_tie(algorithm, own_seal, new_seal),
+			ChainPowAlgorithm::MemoryHard(algorithm) => algorithm.break_tie(own_seal, new_seal),
+		}
+	}
+
+	fn verify(
+		&self,
+		parent: &BlockId<B>,
+		pre_hash: &H256,
+		pre_digest: Option<&[u8]>,
+		seal: &RawSeal,
+		difficulty: U256,
+	) -> Result<bool, PowError<B>> {
+		match self {
+			ChainPowAlgorithm::Hash(algorithm) =>
+				algorithm.verify(parent, pre_hash, pre_digest, seal, difficulty),
+			ChainPowAlgorithm::MemoryHard(algorithm) =>
+				algorithm.verify(parent, pre_hash, pre_digest, seal, difficulty),
+		}
+	}
+
+	fn preliminary_verify(
+		&self,
+		pre_hash: &H256,
+		seal: &RawSeal,
+	) -> Result<Option<bool>, PowError<B>> {
+		match self {
+			ChainPowAlgorithm::Hash(algorithm) =>
+				PowAlgorithm::<B>::preliminary_verify(algorithm, pre_hash, seal),
+			ChainPowAlgorithm::MemoryHard(algorithm) =>
+				algorithm.preliminary_verify(pre_hash, seal),
 		}
+	}
+}
+
+// Hasher of a mining job of the chain's algorithm
+#[derive(Clone)]
+pub enum ChainHasher {
+	Hash(&'static (dyn PowHasher + Sync)),
+	MemoryHard(Dataset),
+}
 
+impl PowHasher for ChainHasher {
+	fn hash(&self, data: &[u8]) -> H256 {
+		match self {
+			ChainHasher::Hash(hasher) => hasher.hash(data),
+			ChainHasher::MemoryHard(dataset) => dataset.hash(data),
+		}
 	}
+}
+
+impl<B, C> JobHasher for ChainPowAlgorithm<B, C>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B> + HeaderMetadata<B, Error = sp_blockchain::Error> + 'static,
+{
+	type Hasher = ChainHasher;
 
+	fn hasher(&self, job: &MiningJob) -> Result<ChainHasher, String> {
+		match self {
+			ChainPowAlgorithm::Hash(algorithm) => algorithm.hasher(job).map(ChainHasher::Hash),
+			ChainPowAlgorithm::MemoryHard(algorithm) =>
+				algorithm.hasher(job).map(ChainHasher::MemoryHard),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use hash::Sha3Hasher;
+	use parity_scale_codec::Encode;
+	use sp_core::Pair;
+
+	fn author() -> app::Pair {
+		app::Pair::from_string("//Alice", None).expect("Static seed is valid; qed")
+	}
+
+	// Find a valid seal by brute force, the difficulties used in the tests are tiny
+	fn mine(pre_hash: H256, difficulty: U256) -> Seal {
+		let solution = (0u64..)
+			.map(|nonce| {
+				Compute { difficulty, pre_hash, nonce: U256::from(nonce) }.compute(&Sha3Hasher)
+			})
+			.find(|solution| hash_meets_difficulty(&solution.work, difficulty))
+			.expect("A seal is found quickly for a tiny difficulty; qed");
+		let signature = author().sign(&solution.signing_payload(&pre_hash));
+		solution.into_seal(signature)
+	}
+
+	#[test]
+	fn preliminary_check_accepts_valid_seal() {
+		let pre_hash = H256::repeat_byte(1);
+		let seal = mine(pre_hash, U256::from(MIN_DIFFICULTY));
+
+		assert!(preliminary_check(&Sha3Hasher, &pre_hash, &seal.encode()));
+	}
+
+	#[test]
+	fn preliminary_check_rejects_garbage() {
+		assert!(!preliminary_check(&Sha3Hasher, &H256::zero(), &vec![1, 2, 3]));
+	}
+
+	#[test]
+	fn preliminary_check_rejects_seal_of_another_block() {
+		let seal = mine(H256::repeat_byte(1), U256::from(MIN_DIFFICULTY));
+
+		assert!(!preliminary_check(&Sha3Hasher, &H256::repeat_byte(2), &seal.encode()));
+	}
+
+	#[test]
+	fn preliminary_check_rejects_forged_work() {
+		let pre_hash = H256::repeat_byte(1);
+		let mut seal = mine(pre_hash, U256::from(MIN_DIFFICULTY));
+		seal.work = H256::zero();
+
+		assert!(!preliminary_check(&Sha3Hasher, &pre_hash, &seal.encode()));
+	}
+
+	#[test]
+	fn preliminary_check_rejects_too_low_difficulty() {
+		let pre_hash = H256::repeat_byte(1);
+		let seal = mine(pre_hash, U256::one());
+
+		assert!(!preliminary_check(&Sha3Hasher, &pre_hash, &seal.encode()));
+	}
+
+	#[test]
+	fn preliminary_check_rejects_zero_difficulty() {
+		let pre_hash = H256::repeat_byte(1);
+		let seal = mine(pre_hash, U256::zero());
+
+		assert!(!preliminary_check(&Sha3Hasher, &pre_hash, &seal.encode()));
+	}
+
+	#[test]
+	fn seal_is_signed_by_author() {
+		let pre_hash = H256::repeat_byte(1);
+		let seal = mine(pre_hash, U256::from(MIN_DIFFICULTY));
+
+		assert!(seal.is_signed_by(&pre_hash, Some(&author().public().encode())));
+	}
+
+	#[test]
+	fn seal_of_another_author_is_rejected() {
+		let pre_hash = H256::repeat_byte(1);
+		let seal = mine(pre_hash, U256::from(MIN_DIFFICULTY));
+		let thief = app::Pair::from_string("//Mallory", None).expect("Static seed is valid; qed");
+
+		assert!(!seal.is_signed_by(&pre_hash, Some(&thief.public().encode())));
+		assert!(!seal.is_signed_by(&pre_hash, None));
+	}
+
+	#[test]
+	fn signature_does_not_carry_over_to_another_block() {
+		let seal = mine(H256::repeat_byte(1), U256::from(MIN_DIFFICULTY));
+
+		assert!(!seal.is_signed_by(&H256::repeat_byte(2), Some(&author().public().encode())));
+	}
+}
@@ -0,0 +1,410 @@
+// Memory-hard PoW algorithm in the spirit of Ethash.
+//
+// Every epoch has a seed derived from an old block hash. The seed expands into a dataset of
+// pseudo random items that has to be kept in memory, and every hash reads many items from it at
+// positions that depend on the previous reads. That makes memory bandwidth, not raw hashing
+// speed, the limit, which keeps mining CPU-friendly.
+//
+// Building a dataset is expensive, so the datasets of recent epochs are kept in a cache that is
+// shared by all the clones of the algorithm: verification and mining use the same datasets.
+//
+// The seed block is looked up in the ancestry of the parent, not on the canonical chain, so that
+// blocks of a fork crossing an epoch boundary are verified with the dataset of their own chain.
+
+use crate::{
+	miner::{JobHasher, MiningJob},
+	hash_meets_difficulty, preliminary_check_work, Compute, PowHasher, PowMetrics, Seal,
+};
+use parity_scale_codec::{Decode, Encode};
+use sc_client_api::{backend::AuxStore, blockchain::HeaderBackend};
+use sc_consensus_pow::{Error as PowError, PowAlgorithm};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderMetadata;
+use sp_consensus_pow::{DifficultyApi, Seal as RawSeal};
+use sp_core::{blake2_256, H256, U256};
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, SaturatedConversion},
+};
+use std::{
+	marker::PhantomData,
+	sync::{Arc, Mutex},
+};
+
+// Number of blocks sharing the same dataset
+pub const EPOCH_LENGTH: u64 = 2048;
+// The seed of an epoch is taken from the block this many blocks before the epoch starts.
+// Forks deeper than that would change the seed and are not supported.
+pub const SEED_LAG: u64 = 256;
+// Number of 32 byte items in a dataset (16 MiB)
+pub const DATASET_ITEMS: usize = 1 << 19;
+// Number of passes mixing the dataset after it is filled
+const DATASET_ROUNDS: usize = 3;
+// Number of dataset reads per hash
+const DATASET_ACCESSES: usize = 64;
+// Number of datasets kept in the cache: the current epoch and the previous one
+const CACHED_DATASETS: usize = 2;
+
+// Dataset of an epoch. Clones share the items.
+#[derive(Clone)]
+pub struct Dataset {
+	seed: H256,
+	items: Arc<Vec<[u8; 32]>>,
+}
+
+impl Dataset {
+	// Expand the seed into a dataset. Takes a noticeable time, use `DatasetCache`.
+	pub fn new(seed: H256) -> Self {
+		let mut items = Vec::with_capacity(DATASET_ITEMS);
+		let mut item = blake2_256(seed.as_bytes());
+		for _ in 0..DATASET_ITEMS {
+			items.push(item);
+			item = blake2_256(&item);
+		}
+
+		// Mix every item with the previous one and a pseudo random other one,
+		// so that no item can be computed without most of the others
+		for _ in 0..DATASET_ROUNDS {
+			for i in 0..DATASET_ITEMS {
+				let previous = items[(i + DATASET_ITEMS - 1) % DATASET_ITEMS];
+				let other = items[item_index(&items[i])];
+				let mut data = [0u8; 64];
+				data[..32].copy_from_slice(&previous);
+				data[32..].copy_from_slice(&other);
+				items[i] = blake2_256(&data);
+			}
+		}
+
+		Self { seed, items: Arc::new(items) }
+	}
+
+	pub fn seed(&self) -> H256 {
+		self.seed
+	}
+}
+
+impl PowHasher for Dataset {
+	fn hash(&self, data: &[u8]) -> H256 {
+		let mut mix = blake2_256(data);
+		for _ in 0..DATASET_ACCESSES {
+			let item = &self.items[item_index(&mix)];
+			let mut data = [0u8; 64];
+			data[..32].copy_from_slice(&mix);
+			data[32..].copy_from_slice(item);
+			mix = blake2_256(&data);
+		}
+		H256::from(mix)
+	}
+}
+
+// Dataset position picked by the first bytes of a hash
+fn item_index(hash: &[u8; 32]) -> usize {
+	u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]]) as usize % DATASET_ITEMS
+}
+
+// Datasets of the latest epochs, shared between clones
+#[derive(Clone, Default)]
+pub struct DatasetCache {
+	datasets: Arc<Mutex<Vec<Dataset>>>,
+	// Held while a dataset is built, so that the import queue and all the mining threads
+	// asking for a new epoch at once wait for a single build
+	building: Arc<Mutex<()>>,
+}
+
+impl DatasetCache {
+	// Get the dataset for the seed, building it if it is not in the cache
+	pub fn get(&self, seed: H256) -> Dataset {
+		if let Some(dataset) = self.find(seed) {
+			return dataset
+		}
+
+		// Build without holding the datasets lock so that other epochs stay available meanwhile
+		let _building = self.building.lock().expect("Dataset build lock is never poisoned; qed");
+		if let Some(dataset) = self.find(seed) {
+			return dataset
+		}
+		let dataset = Dataset::new(seed);
+
+		let mut datasets = self.datasets.lock().expect("Dataset cache lock is never poisoned; qed");
+		if datasets.len() == CACHED_DATASETS {
+			datasets.remove(0);
+		}
+		datasets.push(dataset.clone());
+		dataset
+	}
+
+	fn find(&self, seed: H256) -> Option<Dataset> {
+		self.datasets
+			.lock()
+			.expect("Dataset cache lock is never poisoned; qed")
+			.iter()
+			.find(|dataset| dataset.seed == seed)
+			.cloned()
+	}
+}
+
+// Height of the block whose hash seeds the epoch of the block at the given height
+pub fn seed_height(height: u64) -> u64 {
+	(height / EPOCH_LENGTH * EPOCH_LENGTH).saturating_sub(SEED_LAG)
+}
+
+// Seed of an epoch from the hash of its seed block
+pub fn epoch_seed(epoch: u64, seed_block: H256) -> H256 {
+	H256::from(blake2_256(&(epoch, seed_block).encode()))
+}
+
+// Memory-hard hashing algorithm
+pub struct MemoryHardAlgorithm<B, C> {
+	client: Arc<C>,
+	cache: DatasetCache,
+	metrics: Option<PowMetrics>,
+	_block: PhantomData<B>,
+}
+
+impl<B, C> MemoryHardAlgorithm<B, C> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client, cache: DatasetCache::default(), metrics: None, _block: PhantomData }
+	}
+
+	// Report import queue metrics to the given registry
+	pub fn with_metrics(
+		mut self,
+		registry: Option<&prometheus_endpoint::Registry>,
+	) -> Result<Self, prometheus_endpoint::PrometheusError> {
+		self.metrics = registry.map(PowMetrics::register).transpose()?;
+		Ok(self)
+	}
+
+	pub fn cache(&self) -> &DatasetCache {
+		&self.cache
+	}
+}
+
+impl<B, C> Clone for MemoryHardAlgorithm<B, C> {
+	fn clone(&self) -> Self {
+		Self {
+			client: self.client.clone(),
+			cache: self.cache.clone(),
+			metrics: self.metrics.clone(),
+			_block: PhantomData,
+		}
+	}
+}
+
+impl<B, C> MemoryHardAlgorithm<B, C>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B> + HeaderMetadata<B, Error = sp_blockchain::Error>,
+{
+	// Dataset to mine or verify a child of the given parent with
+	pub fn dataset(&self, parent: &BlockId<B>) -> Result<Dataset, PowError<B>> {
+		let parent = self
+			.client
+			.block_hash_from_id(parent)
+			.map_err(|e| PowError::Environment(format!("Fetching parent hash failed: {:?}", e)))?
+			.ok_or_else(|| PowError::Environment(format!("Unknown parent {:?}", parent)))?;
+
+		self.dataset_at(parent)
+	}
+
+	// Dataset to mine or verify a child of the parent with the given hash
+	pub fn dataset_at(&self, parent: H256) -> Result<Dataset, PowError<B>> {
+		let parent_height: u64 = self.header_metadata(parent)?.number.saturated_into();
+		let height = parent_height + 1;
+
+		let seed_block = self.ancestor_at(parent, seed_height(height))?;
+
+		Ok(self.cache.get(epoch_seed(height / EPOCH_LENGTH, seed_block)))
+	}
+
+	// Hash of the ancestor of `block` at the given height. Walks the parents of `block` back
+	// until the walk joins the canonical chain, where the ancestor can be looked up by number.
+	fn ancestor_at(&self, mut block: H256, height: u64) -> Result<H256, PowError<B>> {
+		loop {
+			let metadata = self.header_metadata(block)?;
+			let number: u64 = metadata.number.saturated_into();
+			if number <= height {
+				return if number == height {
+					Ok(block)
+				} else {
+					Err(PowError::Environment(format!("Block {:?} is below #{}", block, height)))
+				}
+			}
+
+			if self.canonical_hash(number)? == Some(block) {
+				return self.canonical_hash(height)?.ok_or_else(|| {
+					PowError::Environment(format!("Missing seed block #{}", height))
+				})
+			}
+
+			block = metadata.parent;
+		}
+	}
+
+	fn header_metadata(
+		&self,
+		block: H256,
+	) -> Result<sp_blockchain::CachedHeaderMetadata<B>, PowError<B>> {
+		self.client.header_metadata(block).map_err(|e| {
+			PowError::Environment(format!("Fetching header of {:?} failed: {:?}", block, e))
+		})
+	}
+
+	fn canonical_hash(&self, number: u64) -> Result<Option<H256>, PowError<B>> {
+		self.client.hash(number.saturated_into()).map_err(|e| {
+			PowError::Environment(format!("Fetching block #{} failed: {:?}", number, e))
+		})
+	}
+}
+
+impl<B: BlockT<Hash = H256>, C> PowAlgorithm<B> for MemoryHardAlgorithm<B, C>
+where
+	C: HeaderBackend<B>
+		+ HeaderMetadata<B, Error = sp_blockchain::Error>
+		+ AuxStore
+		+ ProvideRuntimeApi<B>,
+	C::Api: DifficultyApi<B, U256>,
+{
+	type Difficulty = U256;
+
+	// Get the next block's difficulty
+	fn difficulty(&self, parent: B::Hash) -> Result<Self::Difficulty, PowError<B>> {
+		let parent_id = BlockId::<B>::hash(parent);
+		self.client.runtime_api().difficulty(&parent_id).map_err(|e| {
+			PowError::Environment(format!("Fetching difficulty from runtime failed: {:?}", e))
+		})
+	}
+
+	// Break a tie situation when choosing a chain fork
+	fn break_tie(&self, own_seal: &RawSeal, new_seal: &RawSeal) -> bool {
+		blake2_256(&own_seal[..]) > blake2_256(&new_seal[..])
+	}
+
+	// Verify that the difficulty is valid against given seal
+	fn verify(
+		&self,
+		parent: &BlockId<B>,
+		pre_hash: &H256,
+		pre_digest: Option<&[u8]>,
+		seal: &RawSeal,
+		difficulty: Self::Difficulty,
+	) -> Result<bool, PowError<B>> {
+		let seal = match Seal::decode(&mut &seal[..]) {
+			Ok(seal) => seal,
+			Err(_) => return Ok(false),
+		};
+
+		if !hash_meets_difficulty(&seal.work, difficulty) {
+			return Ok(false)
+		}
+
+		let dataset = self.dataset(parent)?;
+		let compute = Compute { difficulty, pre_hash: *pre_hash, nonce: seal.nonce };
+
+		if compute.compute(&dataset) != seal.solution() {
+			return Ok(false)
+		}
+
+		Ok(seal.is_signed_by(pre_hash, pre_digest))
+	}
+
+	// Reject bad seals before the expensive verification runs. The work can not be recomputed
+	// without the parent, which picks the dataset, so only the checks that need no hasher run.
+	fn preliminary_verify(
+		&self,
+		_pre_hash: &H256,
+		seal: &RawSeal,
+	) -> Result<Option<bool>, PowError<B>> {
+		let passed = preliminary_check_work(seal).is_some();
+
+		if let Some(metrics) = &self.metrics {
+			metrics.preliminary_checked.inc();
+			if !passed {
+				metrics.preliminary_rejected.inc();
+			}
+		}
+
+		// A seal passing the check still has to go through the full verification
+		Ok(if passed { None } else { Some(false) })
+	}
+}
+
+impl<B, C> JobHasher for MemoryHardAlgorithm<B, C>
+where
+	B: BlockT<Hash = H256>,
+	C: HeaderBackend<B> + HeaderMetadata<B, Error = sp_blockchain::Error> + 'static,
+{
+	type Hasher = Dataset;
+
+	// The first job of an epoch waits for its dataset to be built
+	fn hasher(&self, job: &MiningJob) -> Result<Dataset, String> {
+		self.dataset_at(job.parent).map_err(|e| e.to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sc_client_api::{backend::NewBlockState, in_mem};
+	use sp_runtime::{
+		testing::{Block as RawBlock, ExtrinsicWrapper, Header},
+		traits::Header as _,
+	};
+
+	type TestBlock = RawBlock<ExtrinsicWrapper<u64>>;
+	type TestChain = in_mem::Blockchain<TestBlock>;
+
+	// Import a block on top of `parent`, `fork` tells apart blocks of the same height
+	fn import(chain: &TestChain, parent: Option<&Header>, fork: u8, best: bool) -> Header {
+		let (number, parent) = match parent {
+			Some(parent) => (parent.number + 1, parent.hash()),
+			None => (0, H256::zero()),
+		};
+		let header =
+			Header::new(number, H256::repeat_byte(fork), H256::zero(), parent, Default::default());
+		let state = if best { NewBlockState::Best } else { NewBlockState::Normal };
+		chain.insert(header.hash(), header.clone(), None, None, state).unwrap();
+		header
+	}
+
+	#[test]
+	fn seed_block_is_taken_from_the_ancestry() {
+		let chain = TestChain::new();
+		let genesis = import(&chain, None, 0, true);
+		let one = import(&chain, Some(&genesis), 0, true);
+		let two = import(&chain, Some(&one), 0, true);
+		let three = import(&chain, Some(&two), 0, true);
+		let fork_two = import(&chain, Some(&one), 1, false);
+		let fork_three = import(&chain, Some(&fork_two), 1, false);
+		let algorithm = MemoryHardAlgorithm::<TestBlock, _>::new(Arc::new(chain));
+
+		assert_eq!(algorithm.ancestor_at(three.hash(), 2).unwrap(), two.hash());
+		assert_eq!(algorithm.ancestor_at(fork_three.hash(), 2).unwrap(), fork_two.hash());
+		// Below the fork point the walk joins the canonical chain
+		assert_eq!(algorithm.ancestor_at(fork_three.hash(), 1).unwrap(), one.hash());
+		assert_eq!(algorithm.ancestor_at(fork_three.hash(), 0).unwrap(), genesis.hash());
+		assert!(algorithm.ancestor_at(two.hash(), 3).is_err());
+	}
+
+	#[test]
+	fn first_epochs_are_seeded_by_genesis() {
+		assert_eq!(seed_height(1), 0);
+		assert_eq!(seed_height(EPOCH_LENGTH - 1), 0);
+		assert_eq!(seed_height(EPOCH_LENGTH), EPOCH_LENGTH - SEED_LAG);
+	}
+
+	#[test]
+	fn seed_block_is_always_an_ancestor() {
+		for height in 1..(3 * EPOCH_LENGTH) {
+			assert!(seed_height(height) < height);
+		}
+	}
+
+	#[test]
+	fn seeds_differ_between_epochs() {
+		let block = H256::repeat_byte(1);
+
+		assert_ne!(epoch_seed(0, block), epoch_seed(1, block));
+		assert_eq!(epoch_seed(1, block), epoch_seed(1, block));
+	}
+}
@@ -0,0 +1,29 @@
+use prometheus_endpoint::{register, Counter, PrometheusError, Registry, U64};
+
+// Import queue metrics of the PoW algorithm
+#[derive(Clone)]
+pub struct PowMetrics {
+	pub preliminary_checked: Counter<U64>,
+	pub preliminary_rejected: Counter<U64>,
+}
+
+impl PowMetrics {
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			preliminary_checked: register(
+				Counter::new(
+					"crain_pow_preliminary_checked_total",
+					"Number of imported headers whose seal went through the preliminary check",
+				)?,
+				registry,
+			)?,
+			preliminary_rejected: register(
+				Counter::new(
+					"crain_pow_preliminary_rejected_total",
+					"Number of imported headers rejected by the preliminary seal check",
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
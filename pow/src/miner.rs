@@ -0,0 +1,245 @@
+use crate::{app, hash_meets_difficulty, Compute, HashAlgorithm, PowHasher, Solution};
+use log::*;
+use parity_scale_codec::{Decode, Encode};
+use sc_consensus_pow::{MiningHandle, PowAlgorithm};
+use sp_api::ProvideRuntimeApi;
+use sp_core::{crypto::Public as _, H256, U256};
+use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_runtime::traits::Block as BlockT;
+use std::{
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	thread,
+	time::Duration,
+};
+
+// How many hashes a thread computes before adding them to the shared counter
+const HASH_COUNT_BATCH: u64 = 1024;
+
+// Work handed out to the mining threads
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MiningJob {
+	// Identifier of the job, changes every time new work is built
+	pub id: usize,
+	// Block the mined block is built on, picks the dataset of the memory-hard algorithm
+	pub parent: H256,
+	pub pre_hash: H256,
+	pub difficulty: U256,
+}
+
+// Hash function the mining threads compute the work of a job with
+pub trait JobHasher: Clone + Send + 'static {
+	type Hasher: PowHasher + Send;
+
+	/// Hasher for the given job. May take a while, e.g. to build the dataset of a new epoch
+	fn hasher(&self, job: &MiningJob) -> Result<Self::Hasher, String>;
+}
+
+impl JobHasher for HashAlgorithm {
+	type Hasher = &'static (dyn PowHasher + Sync);
+
+	fn hasher(&self, _job: &MiningJob) -> Result<Self::Hasher, String> {
+		self.plain_hasher()
+			.ok_or_else(|| format!("The {} algorithm can only be mined by the node", self))
+	}
+}
+
+// Anything the mining threads can take work from and send solutions to
+pub trait WorkSource {
+	/// Identifier of the latest job. Must be cheap as it is checked before every hash
+	fn job_id(&self) -> usize;
+
+	/// Current work to mine on, if there is any
+	fn work(&self) -> Option<MiningJob>;
+
+	/// Submit a found solution. Returns `true` if the block was sealed with it
+	fn submit(&self, solution: Solution) -> bool;
+}
+
+// Mining worker of the node. Seals the solutions it gets with the author key,
+// which never leaves the node's keystore.
+pub struct SigningWorker<H> {
+	handle: H,
+	author: app::Public,
+	keystore: SyncCryptoStorePtr,
+}
+
+impl<H> SigningWorker<H> {
+	pub fn new(handle: H, author: app::Public, keystore: SyncCryptoStorePtr) -> Self {
+		Self { handle, author, keystore }
+	}
+
+	// Sign the solution found for the given pre_hash
+	fn sign(&self, pre_hash: &H256, solution: &Solution) -> Option<app::Signature> {
+		let payload = solution.signing_payload(pre_hash);
+		let signature = SyncCryptoStore::sign_with(
+			&*self.keystore,
+			app::ID,
+			&self.author.to_public_crypto_pair(),
+			&payload,
+		);
+
+		match signature {
+			Ok(Some(signature)) => app::Signature::decode(&mut &signature[..]).ok(),
+			Ok(None) => {
+				error!("The author key is not in the keystore, unable to seal blocks");
+				None
+			},
+			Err(e) => {
+				error!("Signing the seal failed: {:?}", e);
+				None
+			},
+		}
+	}
+}
+
+impl<H: Clone> Clone for SigningWorker<H> {
+	fn clone(&self) -> Self {
+		Self {
+			handle: self.handle.clone(),
+			author: self.author.clone(),
+			keystore: self.keystore.clone(),
+		}
+	}
+}
+
+impl<B, A, C, L, Proof> WorkSource for SigningWorker<MiningHandle<B, A, C, L, Proof>>
+where
+	B: BlockT<Hash = H256>,
+	A: PowAlgorithm<B, Difficulty = U256>,
+	C: ProvideRuntimeApi<B>,
+	L: sc_consensus::JustificationSyncLink<B>,
+	sp_api::TransactionFor<C, B>: Send + 'static,
+{
+	fn job_id(&self) -> usize {
+		// The worker bumps its version on every new build and every accepted seal
+		self.handle.version()
+	}
+
+	fn work(&self) -> Option<MiningJob> {
+		let id = self.handle.version();
+		self.handle.metadata().map(|metadata| MiningJob {
+			id,
+			parent: metadata.best_hash,
+			pre_hash: metadata.pre_hash,
+			difficulty: metadata.difficulty,
+		})
+	}
+
+	fn submit(&self, solution: Solution) -> bool {
+		// The solution can only be for the block the worker is building right now
+		let pre_hash = match self.handle.metadata() {
+			Some(metadata) => metadata.pre_hash,
+			None => return false,
+		};
+		let signature = match self.sign(&pre_hash, &solution) {
+			Some(signature) => signature,
+			None => return false,
+		};
+
+		let seal = solution.into_seal(signature);
+		futures::executor::block_on(self.handle.submit(seal.encode()))
+	}
+}
+
+// First nonce of the part of the nonce space searched by the thread with the given index.
+// The space is split into `threads` equal parts, the last thread also takes the remainder.
+pub fn nonce_range_start(index: usize, threads: usize) -> U256 {
+	(U256::MAX / U256::from(threads)) * U256::from(index)
+}
+
+// Spawn `threads` mining threads working on the given source with the given hasher.
+// Every thread searches its own part of the nonce space. As soon as the source has a new
+// job (a new best block arrived or any thread found a seal) all the threads drop what they
+// were doing and start over from the beginning of their parts.
+// Returns a counter of the hashes computed by all the threads, useful to measure the hashrate.
+pub fn start_mining_threads<W, H>(source: W, hasher: H, threads: usize) -> Arc<AtomicU64>
+where
+	W: WorkSource + Clone + Send + 'static,
+	H: JobHasher,
+{
+	let threads = threads.max(1);
+	let hashes = Arc::new(AtomicU64::new(0));
+
+	info!("Starting {} mining thread(s)", threads);
+
+	for index in 0..threads {
+		let source = source.clone();
+		let hasher = hasher.clone();
+		let hashes = hashes.clone();
+		let start = nonce_range_start(index, threads);
+		let end =
+			if index + 1 == threads { U256::MAX } else { nonce_range_start(index + 1, threads) };
+
+		thread::Builder::new()
+			.name(format!("crain-miner-{}", index))
+			.spawn(move || {
+				let mut job: Option<MiningJob> = None;
+				let mut job_hasher: Option<H::Hasher> = None;
+				let mut nonce = start;
+				let mut computed = 0;
+				loop {
+					let stale = match &job {
+						Some(job) => job.id != source.job_id(),
+						None => true,
+					};
+					if stale {
+						job = source.work();
+						job_hasher = None;
+						nonce = start;
+						match &job {
+							Some(new_job) => {
+								trace!(
+									"Mining thread {} switched to job {} ({:?})",
+									index,
+									new_job.id,
+									new_job.pre_hash
+								);
+								match hasher.hasher(new_job) {
+									Ok(new_hasher) => job_hasher = Some(new_hasher),
+									Err(e) => {
+										warn!("Mining thread {} can not mine: {}", index, e);
+										// Try again with whatever work there is then
+										job = None;
+										thread::sleep(Duration::new(1, 0));
+										continue
+									},
+								}
+							},
+							None => {
+								thread::sleep(Duration::new(1, 0));
+								continue
+							},
+						}
+					}
+
+					let (difficulty, pre_hash, job_hasher) = match (&job, &job_hasher) {
+						(Some(job), Some(job_hasher)) => (job.difficulty, job.pre_hash, job_hasher),
+						_ => continue,
+					};
+
+					let solution = Compute { difficulty, pre_hash, nonce }.compute(job_hasher);
+					computed += 1;
+					if computed == HASH_COUNT_BATCH {
+						hashes.fetch_add(computed, Ordering::Relaxed);
+						computed = 0;
+					}
+					if hash_meets_difficulty(&solution.work, solution.difficulty) {
+						if !source.submit(solution) {
+							debug!("Mining thread {} found a solution that was not accepted", index);
+						}
+					}
+
+					nonce = nonce.saturating_add(U256::from(1));
+					if nonce >= end {
+						nonce = start;
+					}
+				}
+			})
+			.expect("Spawning a mining thread must not fail; qed");
+	}
+
+	hashes
+}
@@ -0,0 +1,262 @@
+use sc_client_api::backend::Backend;
+use sc_consensus_pow::PowAux;
+use sp_blockchain::{Backend as _, HeaderBackend};
+use sp_consensus::{Error as ConsensusError, SelectChain};
+use sp_core::U256;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::{marker::PhantomData, sync::Arc};
+
+// Fork choice rule for PoW chains: the best chain is the one with the most total work.
+// Total difficulty of every block is recorded in aux storage by `sc_consensus_pow` on import.
+pub struct HeaviestChain<B, Block> {
+	backend: Arc<B>,
+	_phantom: PhantomData<Block>,
+}
+
+impl<B, Block> Clone for HeaviestChain<B, Block> {
+	fn clone(&self) -> Self {
+		Self { backend: self.backend.clone(), _phantom: PhantomData }
+	}
+}
+
+impl<B, Block> HeaviestChain<B, Block>
+where
+	B: Backend<Block>,
+	Block: BlockT,
+{
+	pub fn new(backend: Arc<B>) -> Self {
+		Self { backend, _phantom: PhantomData }
+	}
+
+	fn best_hash(&self) -> sp_blockchain::Result<Block::Hash> {
+		let blockchain = self.backend.blockchain();
+		let leaves = blockchain.leaves()?;
+		let best_hash = blockchain.info().best_hash;
+
+		let heaviest = heaviest_leaf(&leaves, best_hash, |hash| {
+			PowAux::<U256>::read::<_, Block>(self.backend.as_ref(), hash)
+				.map(|aux| aux.total_difficulty)
+				.map_err(|e| sp_blockchain::Error::Backend(e.to_string()))
+		})?;
+
+		Ok(heaviest.unwrap_or(best_hash))
+	}
+}
+
+#[async_trait::async_trait]
+impl<B, Block> SelectChain<Block> for HeaviestChain<B, Block>
+where
+	B: Backend<Block>,
+	Block: BlockT,
+{
+	async fn leaves(&self) -> Result<Vec<Block::Hash>, ConsensusError> {
+		self.backend
+			.blockchain()
+			.leaves()
+			.map_err(|e| ConsensusError::ChainLookup(e.to_string()))
+	}
+
+	async fn best_chain(&self) -> Result<Block::Header, ConsensusError> {
+		let best_hash =
+			self.best_hash().map_err(|e| ConsensusError::ChainLookup(e.to_string()))?;
+
+		self.backend
+			.blockchain()
+			.header(BlockId::Hash(best_hash))
+			.map_err(|e| ConsensusError::ChainLookup(e.to_string()))?
+			.ok_or_else(|| ConsensusError::ChainLookup(format!("Missing header {:?}", best_hash)))
+	}
+}
+
+// Pick the leaf with the greatest total difficulty.
+// On a tie the current best block wins, then the leaf that comes first.
+pub fn heaviest_leaf<H, E>(
+	leaves: &[H],
+	current_best: H,
+	total_difficulty: impl Fn(&H) -> Result<U256, E>,
+) -> Result<Option<H>, E>
+where
+	H: PartialEq + Copy,
+{
+	let mut heaviest: Option<(H, U256)> = None;
+
+	for leaf in leaves {
+		let difficulty = total_difficulty(leaf)?;
+		let replace = match heaviest {
+			None => true,
+			Some((_, best_difficulty)) if difficulty > best_difficulty => true,
+			Some((_, best_difficulty)) => difficulty == best_difficulty && *leaf == current_best,
+		};
+		if replace {
+			heaviest = Some((*leaf, difficulty));
+		}
+	}
+
+	Ok(heaviest.map(|(leaf, _)| leaf))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parity_scale_codec::Encode;
+	use sc_client_api::{backend::NewBlockState, in_mem, AuxStore};
+	use sc_consensus_pow::POW_AUX_PREFIX;
+	use sp_core::H256;
+	use sp_runtime::{
+		testing::{Block as RawBlock, ExtrinsicWrapper, Header},
+		traits::Header as _,
+	};
+	use std::collections::HashMap;
+
+	// Block tree of `(hash, parent, difficulty)`, genesis has the hash 0
+	fn total_difficulties(blocks: &[(u64, u64, u64)]) -> HashMap<u64, U256> {
+		let mut totals = HashMap::new();
+		totals.insert(0, U256::zero());
+		for (hash, parent, difficulty) in blocks {
+			let total = totals[parent] + U256::from(*difficulty);
+			totals.insert(*hash, total);
+		}
+		totals
+	}
+
+	fn select(blocks: &[(u64, u64, u64)], leaves: &[u64], best: u64) -> Option<u64> {
+		let totals = total_difficulties(blocks);
+		heaviest_leaf(leaves, best, |hash| Ok::<_, ()>(totals[hash])).unwrap()
+	}
+
+	#[test]
+	fn short_but_heavier_fork_wins() {
+		// Long fork: 5 blocks of difficulty 10 on top of block 1, 60 in total
+		// Short fork: 2 blocks of difficulty 30 on top of block 1, 70 in total
+		let blocks = [
+			(1, 0, 10),
+			(2, 1, 10),
+			(3, 2, 10),
+			(4, 3, 10),
+			(5, 4, 10),
+			(6, 5, 10),
+			(12, 1, 30),
+			(13, 12, 30),
+		];
+
+		assert_eq!(select(&blocks, &[6, 13], 6), Some(13));
+		assert_eq!(select(&blocks, &[13, 6], 6), Some(13));
+	}
+
+	#[test]
+	fn long_fork_wins_with_more_work() {
+		let blocks = [(1, 0, 10), (2, 1, 10), (3, 2, 10), (12, 1, 15)];
+
+		assert_eq!(select(&blocks, &[3, 12], 12), Some(3));
+	}
+
+	#[test]
+	fn tie_keeps_current_best() {
+		let blocks = [(1, 0, 10), (2, 1, 10), (12, 1, 10)];
+
+		assert_eq!(select(&blocks, &[2, 12], 12), Some(12));
+		assert_eq!(select(&blocks, &[2, 12], 2), Some(2));
+		// Neither leaf is the current best, the first one wins
+		assert_eq!(select(&blocks, &[12, 2], 1), Some(12));
+	}
+
+	#[test]
+	fn no_leaves() {
+		assert_eq!(select(&[], &[], 0), None);
+	}
+
+	type TestBlock = RawBlock<ExtrinsicWrapper<u64>>;
+	type TestBackend = in_mem::Backend<TestBlock>;
+
+	fn genesis(backend: &TestBackend) -> Header {
+		let header = Header::new(0, H256::zero(), H256::zero(), H256::zero(), Default::default());
+		backend
+			.blockchain()
+			.insert(header.hash(), header.clone(), None, None, NewBlockState::Final)
+			.unwrap();
+		header
+	}
+
+	// Import a block of the given fork on top of `parent`. Records its total difficulty the way
+	// `sc_consensus_pow` does, `best` makes it the best block of the client.
+	fn import(
+		backend: &TestBackend,
+		parent: &Header,
+		fork: u8,
+		difficulty: u64,
+		best: bool,
+	) -> Header {
+		let header = Header::new(
+			parent.number + 1,
+			H256::zero(),
+			H256::repeat_byte(fork),
+			parent.hash(),
+			Default::default(),
+		);
+
+		let parent_aux = PowAux::<U256>::read::<_, TestBlock>(backend, &parent.hash()).unwrap();
+		let aux = PowAux {
+			difficulty: U256::from(difficulty),
+			total_difficulty: parent_aux.total_difficulty + U256::from(difficulty),
+		};
+		let key = POW_AUX_PREFIX.iter().chain(header.hash().as_ref()).copied().collect::<Vec<_>>();
+		backend.insert_aux(&[(&key[..], &aux.encode()[..])], &[]).unwrap();
+
+		let state = if best { NewBlockState::Best } else { NewBlockState::Normal };
+		backend
+			.blockchain()
+			.insert(header.hash(), header.clone(), None, None, state)
+			.unwrap();
+		header
+	}
+
+	fn best_chain(chain: &HeaviestChain<TestBackend, TestBlock>) -> Header {
+		futures::executor::block_on(chain.best_chain()).unwrap()
+	}
+
+	#[test]
+	fn best_chain_follows_the_most_work_in_a_backend() {
+		let backend = Arc::new(TestBackend::new());
+		let chain = HeaviestChain::new(backend.clone());
+		let genesis = genesis(&backend);
+		assert_eq!(best_chain(&chain), genesis);
+
+		// Long fork of 5 blocks of difficulty 10, the client takes it as best
+		let first = import(&backend, &genesis, 1, 10, true);
+		let mut long = first.clone();
+		for _ in 0..4 {
+			long = import(&backend, &long, 1, 10, true);
+		}
+		// Short fork of 2 blocks of difficulty 30 on top of the first block, 70 in total
+		let short = import(&backend, &first, 2, 30, false);
+		let short = import(&backend, &short, 2, 30, false);
+
+		assert_eq!(backend.blockchain().info().best_hash, long.hash());
+		assert_eq!(best_chain(&chain), short);
+		assert_eq!(
+			futures::executor::block_on(chain.leaves()).unwrap().len(),
+			2,
+			"Both forks are leaves"
+		);
+
+		// Both forks at 70, the best block of the client wins the tie
+		long = import(&backend, &long, 1, 10, true);
+		long = import(&backend, &long, 1, 10, true);
+		assert_eq!(best_chain(&chain), long);
+
+		// And takes over with more work
+		long = import(&backend, &long, 1, 10, false);
+		assert_eq!(best_chain(&chain), long);
+		assert_ne!(best_chain(&chain), short);
+	}
+
+	#[test]
+	fn lookup_errors_are_returned() {
+		let result = heaviest_leaf(&[1u64, 2], 1, |hash| match hash {
+			2 => Err("missing"),
+			_ => Ok(U256::one()),
+		});
+
+		assert_eq!(result, Err("missing"));
+	}
+}
@@ -0,0 +1,116 @@
+//! Money supply schedule of the block rewards.
+
+use crate::YEAR_HEIGHT;
+
+/// Smallest units in a coin.
+pub const COIN: u128 = 1_000_000_000_000;
+/// Reward of the first blocks.
+pub const INITIAL_REWARD: u128 = 50 * COIN;
+/// The reward halves every 4 years
+pub const HALVING_INTERVAL: u64 = 4 * YEAR_HEIGHT;
+/// No more than this is ever minted by the halving emission.
+pub const SUPPLY_CAP: u128 = 210_000_000 * COIN;
+
+/// Emission schedule of the network.
+pub const EMISSION: EmissionSchedule = EmissionSchedule {
+	initial_reward: INITIAL_REWARD,
+	halving_interval: HALVING_INTERVAL,
+	supply_cap: SUPPLY_CAP,
+	tail_reward: None,
+};
+
+/// Block reward that halves every `halving_interval` blocks until `supply_cap` is minted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EmissionSchedule {
+	/// Reward of the blocks before the first halving.
+	pub initial_reward: u128,
+	/// Number of blocks between two halvings.
+	pub halving_interval: u64,
+	/// Total amount the halving emission stops at.
+	pub supply_cap: u128,
+	/// Tail emission. When set, the reward never drops below it, not even past the cap,
+	/// so miners are still paid once the halvings have run out.
+	pub tail_reward: Option<u128>,
+}
+
+impl EmissionSchedule {
+	/// Reward of the block at `height` before it is bounded by the supply.
+	pub fn halving_reward(&self, height: u64) -> u128 {
+		let halvings = height / self.halving_interval.max(1);
+		if halvings >= u128::BITS as u64 {
+			return 0
+		}
+		self.initial_reward >> halvings
+	}
+
+	/// Reward of the block at `height`, given the amount minted by all the blocks before it.
+	pub fn reward(&self, height: u64, minted: u128) -> u128 {
+		let reward = self.halving_reward(height).min(self.supply_cap.saturating_sub(minted));
+		match self.tail_reward {
+			Some(tail_reward) => reward.max(tail_reward),
+			None => reward,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SCHEDULE: EmissionSchedule = EmissionSchedule {
+		initial_reward: 100,
+		halving_interval: 10,
+		supply_cap: 1500,
+		tail_reward: None,
+	};
+
+	#[test]
+	fn reward_halves_every_interval() {
+		assert_eq!(SCHEDULE.reward(0, 0), 100);
+		assert_eq!(SCHEDULE.reward(9, 0), 100);
+		assert_eq!(SCHEDULE.reward(10, 0), 50);
+		assert_eq!(SCHEDULE.reward(20, 0), 25);
+		assert_eq!(SCHEDULE.reward(70, 0), 0);
+	}
+
+	#[test]
+	fn reward_runs_out_after_all_halvings() {
+		assert_eq!(SCHEDULE.reward(10 * 200, 0), 0);
+		assert_eq!(SCHEDULE.reward(u64::MAX, 0), 0);
+	}
+
+	#[test]
+	fn reward_is_bounded_by_cap() {
+		assert_eq!(SCHEDULE.reward(0, 1450), 50);
+		assert_eq!(SCHEDULE.reward(0, 1500), 0);
+		assert_eq!(SCHEDULE.reward(0, 2000), 0);
+	}
+
+	#[test]
+	fn total_supply_never_exceeds_cap() {
+		let schedule = EmissionSchedule { supply_cap: 1234, ..SCHEDULE };
+		let minted = (0..1000).fold(0, |minted, height| minted + schedule.reward(height, minted));
+
+		assert_eq!(minted, 1234);
+	}
+
+	#[test]
+	fn tail_emission_is_a_floor() {
+		let schedule = EmissionSchedule { tail_reward: Some(30), ..SCHEDULE };
+
+		assert_eq!(schedule.reward(0, 0), 100);
+		assert_eq!(schedule.reward(20, 0), 30);
+		assert_eq!(schedule.reward(u64::MAX, 0), 30);
+		assert_eq!(schedule.reward(0, 1500), 30);
+	}
+
+	#[test]
+	fn default_schedule_stays_below_cap() {
+		let halvings = u128::BITS as u64;
+		let minted: u128 = (0..halvings)
+			.map(|era| EMISSION.halving_reward(era * HALVING_INTERVAL) * HALVING_INTERVAL as u128)
+			.sum();
+
+		assert!(minted <= SUPPLY_CAP);
+	}
+}
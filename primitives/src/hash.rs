@@ -0,0 +1,208 @@
+//! Hash functions the PoW can be computed with.
+
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256, Sha3_256};
+use sp_core::{blake2_256, H256};
+use sp_std::fmt;
+
+// Hashing strategy used to compute the work of a seal
+pub trait PowHasher {
+	fn hash(&self, data: &[u8]) -> H256;
+}
+
+// SHA3-256
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Sha3Hasher;
+
+impl PowHasher for Sha3Hasher {
+	fn hash(&self, data: &[u8]) -> H256 {
+		H256::from_slice(Sha3_256::digest(data).as_slice())
+	}
+}
+
+// Blake2b-256
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Blake2bHasher;
+
+impl PowHasher for Blake2bHasher {
+	fn hash(&self, data: &[u8]) -> H256 {
+		H256::from(blake2_256(data))
+	}
+}
+
+// Keccak-256, the pre-standard SHA3 used by Ethereum
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct KeccakHasher;
+
+impl PowHasher for KeccakHasher {
+	fn hash(&self, data: &[u8]) -> H256 {
+		H256::from_slice(Keccak256::digest(data).as_slice())
+	}
+}
+
+// PoW algorithm of a chain, set in the runtime genesis and read through `PowAlgorithmApi`
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize), serde(rename_all = "lowercase"))]
+pub enum HashAlgorithm {
+	Sha3,
+	Blake2b,
+	Keccak,
+	// Hashes with the dataset of an epoch, see `crain_pow::memory_hard`.
+	// Only the node has the datasets, so it has no plain hasher.
+	#[cfg_attr(feature = "std", serde(rename = "memory-hard"))]
+	MemoryHard,
+}
+
+impl Default for HashAlgorithm {
+	fn default() -> Self {
+		HashAlgorithm::Sha3
+	}
+}
+
+impl HashAlgorithm {
+	// Hasher of the plain hash functions, `None` for the memory-hard algorithm
+	pub fn plain_hasher(self) -> Option<&'static (dyn PowHasher + Sync)> {
+		match self {
+			HashAlgorithm::Sha3 => Some(&Sha3Hasher),
+			HashAlgorithm::Blake2b => Some(&Blake2bHasher),
+			HashAlgorithm::Keccak => Some(&KeccakHasher),
+			HashAlgorithm::MemoryHard => None,
+		}
+	}
+}
+
+impl<H: PowHasher + ?Sized> PowHasher for &H {
+	fn hash(&self, data: &[u8]) -> H256 {
+		(**self).hash(data)
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::str::FromStr for HashAlgorithm {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"sha3" => Ok(HashAlgorithm::Sha3),
+			"blake2b" => Ok(HashAlgorithm::Blake2b),
+			"keccak" => Ok(HashAlgorithm::Keccak),
+			"memory-hard" => Ok(HashAlgorithm::MemoryHard),
+			_ => Err(format!("Unknown PoW hash algorithm: {}", s)),
+		}
+	}
+}
+
+impl fmt::Display for HashAlgorithm {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let name = match self {
+			HashAlgorithm::Sha3 => "sha3",
+			HashAlgorithm::Blake2b => "blake2b",
+			HashAlgorithm::Keccak => "keccak",
+			HashAlgorithm::MemoryHard => "memory-hard",
+		};
+		f.write_str(name)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::seal::Compute;
+	use sp_core::U256;
+
+	const PLAIN_ALGORITHMS: [HashAlgorithm; 3] =
+		[HashAlgorithm::Sha3, HashAlgorithm::Blake2b, HashAlgorithm::Keccak];
+
+	fn h256(hex: &str) -> H256 {
+		let bytes = (0..hex.len())
+			.step_by(2)
+			.map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("Static hex is valid; qed"))
+			.collect::<Vec<_>>();
+		H256::from_slice(&bytes)
+	}
+
+	// Published digests of "" and "abc"
+	fn known_digests(algorithm: HashAlgorithm) -> [(&'static [u8], H256); 2] {
+		let (empty, abc) = match algorithm {
+			HashAlgorithm::Sha3 => (
+				"a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a",
+				"3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+			),
+			HashAlgorithm::Blake2b => (
+				"0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8",
+				"bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319",
+			),
+			HashAlgorithm::Keccak => (
+				"c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470",
+				"4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45",
+			),
+			HashAlgorithm::MemoryHard => unreachable!("Memory-hard has no digests"),
+		};
+		[(&b""[..], h256(empty)), (&b"abc"[..], h256(abc))]
+	}
+
+	// Work of difficulty 1000, pre_hash 0x0101..01 and nonce 42
+	fn known_work(algorithm: HashAlgorithm) -> H256 {
+		h256(match algorithm {
+			HashAlgorithm::Sha3 =>
+				"7fdeda3efe59fab9b1c9b2d96ed41725dc2b7ec9b4c08a3fcb6a4bcc833ba067",
+			HashAlgorithm::Blake2b =>
+				"7fc2ed1d3bfbc52f605240dfc64c21661b1e3dacc3f9908ec0d3276914c4cc0e",
+			HashAlgorithm::Keccak =>
+				"4512bb2f2b10e950b3915d96ef7073ca06d51641a61e5ba1c490be514489c565",
+			HashAlgorithm::MemoryHard => unreachable!("Memory-hard has no plain work"),
+		})
+	}
+
+	#[test]
+	fn hashers_match_known_digests() {
+		for (data, digest) in known_digests(HashAlgorithm::Sha3) {
+			assert_eq!(Sha3Hasher.hash(data), digest);
+		}
+		for (data, digest) in known_digests(HashAlgorithm::Blake2b) {
+			assert_eq!(Blake2bHasher.hash(data), digest);
+		}
+		for (data, digest) in known_digests(HashAlgorithm::Keccak) {
+			assert_eq!(KeccakHasher.hash(data), digest);
+		}
+	}
+
+	#[test]
+	fn every_algorithm_matches_known_digests() {
+		for algorithm in PLAIN_ALGORITHMS {
+			let hasher = algorithm.plain_hasher().expect("Plain algorithms have a hasher; qed");
+			for (data, digest) in known_digests(algorithm) {
+				assert_eq!(hasher.hash(data), digest, "{} of {:?}", algorithm, data);
+			}
+		}
+	}
+
+	#[test]
+	fn every_algorithm_computes_known_work() {
+		for algorithm in PLAIN_ALGORITHMS {
+			let hasher = algorithm.plain_hasher().expect("Plain algorithms have a hasher; qed");
+			let compute = Compute {
+				difficulty: U256::from(1000),
+				pre_hash: H256::repeat_byte(1),
+				nonce: U256::from(42),
+			};
+			assert_eq!(compute.compute(hasher).work, known_work(algorithm), "{}", algorithm);
+		}
+	}
+
+	#[test]
+	fn memory_hard_has_no_plain_hasher() {
+		assert!(HashAlgorithm::MemoryHard.plain_hasher().is_none());
+	}
+
+	#[test]
+	fn names_round_trip() {
+		for algorithm in PLAIN_ALGORITHMS.iter().chain([HashAlgorithm::MemoryHard].iter()) {
+			assert_eq!(algorithm.to_string().parse::<HashAlgorithm>(), Ok(*algorithm));
+		}
+		assert!("sha256".parse::<HashAlgorithm>().is_err());
+	}
+}
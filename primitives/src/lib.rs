@@ -2,8 +2,29 @@
 
 //! Crain primitive constants and types.
 
+pub mod emission;
+pub mod hash;
+pub mod seal;
+
+use crate::hash::HashAlgorithm;
+use parity_scale_codec::{Codec, Decode, Encode, MaxEncodedLen};
+use scale_info::TypeInfo;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_std::vec::Vec;
+
 pub type Difficulty = sp_core::U256;
 
+/// Key of the block author, put into the PoW pre-runtime digest by the miner.
+pub mod app {
+	use sp_application_crypto::{app_crypto, sr25519};
+	use sp_core::crypto::KeyTypeId;
+
+	pub const ID: KeyTypeId = KeyTypeId(*b"crn1");
+
+	app_crypto!(sr25519, ID);
+}
+
 /// Block interval, in seconds, the network will tune its next_target for.
 pub const BLOCK_TIME_SEC: u64 = 60;
 /// Block time interval in milliseconds.
@@ -25,8 +46,62 @@ pub const DIFFICULTY_ADJUST_WINDOW: u64 = HOUR_HEIGHT;
 pub const CLAMP_FACTOR: u128 = 2;
 /// Dampening factor to use for difficulty adjustment
 pub const DIFFICULTY_DAMP_FACTOR: u128 = 3;
+/// Half-life, in blocks, of the exponential difficulty adjustment
+pub const DIFFICULTY_HALF_LIFE: u32 = HOUR_HEIGHT as u32;
 /// Minimum difficulty, enforced in diff retargetting
 /// avoids getting stuck when trying to increase difficulty subject to dampening
 pub const MIN_DIFFICULTY: u128 = DIFFICULTY_DAMP_FACTOR;
 /// Maximum difficulty.
-pub const MAX_DIFFICULTY: u128 = u128::max_value();
\ No newline at end of file
+pub const MAX_DIFFICULTY: u128 = u128::max_value();
+
+/// Mined reward that is locked until the block it matures at.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, TypeInfo, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize), serde(rename_all = "camelCase"))]
+pub struct ImmatureReward<BlockNumber, Balance> {
+	/// Block the reward can be spent from.
+	pub mature_at: BlockNumber,
+	/// Amount of the reward.
+	pub amount: Balance,
+}
+
+/// Difficulty a block was mined at and its timestamp.
+#[derive(Encode, Decode, TypeInfo, MaxEncodedLen, Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct DifficultyAndTimestamp<M> {
+	pub difficulty: Difficulty,
+	pub timestamp: M,
+}
+
+sp_api::decl_runtime_apis! {
+	/// Block rewards of the chain.
+	pub trait RewardsApi<AccountId: Codec, BlockNumber: Codec, Balance: Codec> {
+		/// Subsidy of the block mined on top of this one.
+		fn current_subsidy() -> Balance;
+		/// Subsidy of the block after that.
+		fn next_subsidy() -> Balance;
+		/// Rewards of the account that are not mature yet, from the oldest.
+		fn immature_rewards(account: AccountId) -> Vec<ImmatureReward<BlockNumber, Balance>>;
+	}
+
+	/// Authors of the blocks.
+	pub trait AuthorshipApi<AccountId: Codec> {
+		/// Account of the author who mined this block, from its PoW pre-runtime digest.
+		fn author() -> Option<AccountId>;
+	}
+
+	/// Difficulty retarget of the chain.
+	pub trait DifficultyHistoryApi<Moment: Codec> {
+		/// Blocks of the retarget window, from earliest to latest.
+		fn difficulty_window() -> Vec<DifficultyAndTimestamp<Moment>>;
+		/// Difficulty after the next block, if it is mined on target.
+		fn predicted_difficulty() -> Difficulty;
+		/// Network hashrate in hashes per second, estimated from the retarget window.
+		fn hashrate() -> Difficulty;
+	}
+
+	/// PoW algorithm of the chain.
+	pub trait PowAlgorithmApi {
+		/// Algorithm the seals of the chain are computed with, set at genesis.
+		fn pow_algorithm() -> HashAlgorithm;
+	}
+}
@@ -0,0 +1,89 @@
+//! Seals of the PoW blocks, shared by the node and the runtime.
+
+use crate::{app, hash::PowHasher};
+use parity_scale_codec::{Decode, Encode};
+use scale_info::TypeInfo;
+use sp_application_crypto::RuntimeAppPublic;
+use sp_core::{H256, U256};
+use sp_std::vec::Vec;
+
+// Check if the given hash satisfies the given difficulty
+// Multiply both together. If the product overflows the bounds of U256 then
+// the hash was to high
+pub fn hash_meets_difficulty(hash: &H256, difficulty: U256) -> bool {
+	let num_hash = U256::from(&hash[..]);
+	let (_, overflowed) = num_hash.overflowing_mul(difficulty);
+
+	!overflowed
+}
+
+
+// A solved PoW, before it is signed by the author
+#[derive(Clone, PartialEq, Eq, Encode, Decode, TypeInfo, Debug)]
+pub struct Solution {
+	pub difficulty: U256,
+	pub work: H256,
+	pub nonce: U256
+}
+
+impl Solution {
+	// Message the author signs to seal the block. The pre_hash covers the pre-runtime digest
+	// with the author, so the work already commits to the author and the signature proves the
+	// author is the one who sealed the block.
+	pub fn signing_payload(&self, pre_hash: &H256) -> Vec<u8> {
+		(pre_hash, self.work).encode()
+	}
+
+	pub fn into_seal(self, signature: app::Signature) -> Seal {
+		Seal { difficulty: self.difficulty, work: self.work, nonce: self.nonce, signature }
+	}
+}
+
+
+// A Seal struct to encode as Vec<u8> and use as the 'RawSeal' type
+#[derive(Clone, PartialEq, Eq, Encode, Decode, TypeInfo, Debug)]
+pub struct Seal {
+	pub difficulty: U256,
+	pub work: H256,
+	pub nonce: U256,
+	// Signature of `Solution::signing_payload` by the author from the pre-runtime digest
+	pub signature: app::Signature,
+}
+
+impl Seal {
+	pub fn solution(&self) -> Solution {
+		Solution { difficulty: self.difficulty, work: self.work, nonce: self.nonce }
+	}
+
+	// Check that the seal is signed by the author from the pre-runtime digest
+	pub fn is_signed_by(&self, pre_hash: &H256, pre_digest: Option<&[u8]>) -> bool {
+		let author = match pre_digest.map(|mut digest| app::Public::decode(&mut digest)) {
+			Some(Ok(author)) => author,
+			_ => return false,
+		};
+
+		let payload = self.solution().signing_payload(pre_hash);
+		author.verify(&payload, &self.signature)
+	}
+}
+
+
+// An attempt to solve a PoW
+#[derive(Clone, PartialEq, Eq, Encode, Decode, TypeInfo, Debug)]
+pub struct Compute {
+	pub difficulty: U256,
+	pub pre_hash: H256,
+	pub nonce: U256
+}
+
+impl Compute {
+	pub fn compute<H: PowHasher + ?Sized>(self, hasher: &H) -> Solution {
+		let work = hasher.hash(&self.encode()[..]);
+
+		Solution {
+			nonce: self.nonce,
+			difficulty: self.difficulty,
+			work,
+		}
+	}
+}
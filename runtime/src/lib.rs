@@ -93,7 +93,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	//   `spec_version`, and `authoring_version` are the same between Wasm and native.
 	// This value is set to 100 to notify Polkadot-JS App (https://polkadot.js.org/apps) to use
 	//   the compatible custom types.
-	spec_version: 100,
+	spec_version: 103,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 1,
@@ -235,7 +235,8 @@ parameter_types! {
 }
 
 impl pallet_transaction_payment::Config for Runtime {
-	type OnChargeTransaction = CurrencyAdapter<Balances, ()>;
+	// Fees and tips go to the block author
+	type OnChargeTransaction = CurrencyAdapter<Balances, pallet_rewards::FeesToAuthor<Runtime>>;
 	type OperationalFeeMultiplier = ConstU8<5>;
 	type WeightToFee = IdentityFee<Balance>;
 	type LengthToFee = IdentityFee<Balance>;
@@ -309,11 +310,66 @@ impl pallet_timestamp::Config for Runtime {
 
 parameter_types! {
 	pub const TargetBlockTime: u64 = BLOCK_TIME;
+	pub const DifficultyWindowSize: u32 = crain_primitives::DIFFICULTY_ADJUST_WINDOW as u32;
+	pub const DifficultyDampFactor: u128 = crain_primitives::DIFFICULTY_DAMP_FACTOR;
+	pub const DifficultyClampFactor: u128 = crain_primitives::CLAMP_FACTOR;
+	pub const DifficultyHalfLife: u32 = crain_primitives::DIFFICULTY_HALF_LIFE;
+	pub const MinDifficulty: u128 = crain_primitives::MIN_DIFFICULTY;
+	pub const MaxDifficulty: u128 = crain_primitives::MAX_DIFFICULTY;
 }
 
 /// Configure Difficulty pallet for Runtime
 impl pallet_difficulty::Config for Runtime {
+	type Event = Event;
 	type TargetBlockTime = TargetBlockTime;
+	// Kept on the damp and clamp retarget the chain started with
+	type DifficultyAlgorithm = pallet_difficulty::DampClamp;
+	type WindowSize = DifficultyWindowSize;
+	type DampFactor = DifficultyDampFactor;
+	type ClampFactor = DifficultyClampFactor;
+	type HalfLife = DifficultyHalfLife;
+	type MinDifficulty = MinDifficulty;
+	type MaxDifficulty = MaxDifficulty;
+	type WeightInfo = pallet_difficulty::weights::SubstrateWeight<Runtime>;
+}
+
+parameter_types! {
+	pub const Emission: crain_primitives::emission::EmissionSchedule =
+		crain_primitives::emission::EMISSION;
+	// There is no treasury yet, so the whole fees go to the author
+	pub const FeeTreasuryShare: Perbill = Perbill::from_percent(0);
+	pub CurrentDifficulty: crain_primitives::Difficulty = Difficulty::difficulty();
+	pub CurrentPowAlgorithm: crain_primitives::hash::HashAlgorithm = Difficulty::pow_algorithm();
+	pub const UncleReward: Perbill = Perbill::from_percent(50);
+	// Mined rewards can be spent 100 blocks later, deep enough to survive a reorg
+	pub const RewardMaturity: BlockNumber = 100;
+}
+
+/// Configure Rewards pallet for Runtime
+impl pallet_rewards::Config for Runtime {
+	type Event = Event;
+	type Currency = Balances;
+	type RewardMaturity = RewardMaturity;
+	type Emission = Emission;
+	type TreasuryShare = FeeTreasuryShare;
+	type Treasury = ();
+	type CurrentDifficulty = CurrentDifficulty;
+	type PowAlgorithm = CurrentPowAlgorithm;
+	type UncleReward = UncleReward;
+	type WeightInfo = pallet_rewards::weights::SubstrateWeight<Runtime>;
+}
+
+/// Configure Authorship pallet for Runtime
+impl pallet_authorship::Config for Runtime {
+	// The author key from the PoW pre-runtime digest, paid to the account of the same key
+	type FindAuthor = pallet_rewards::FindPowAuthor<pallet_rewards::PublicToAccount>;
+	// Siblings of the last 5 blocks can be included as uncles
+	type UncleGenerations = ConstU32<5>;
+	type FilterUncle = pallet_authorship::OnePerAuthorPerHeight<
+		pallet_rewards::VerifyPowSeal<Runtime>,
+		BlockNumber,
+	>;
+	type EventHandler = Rewards;
 }
 
 // Declare constants for Nicks pallet
@@ -352,9 +408,12 @@ construct_runtime!(
 		TransactionPayment: pallet_transaction_payment,
 		Sudo: pallet_sudo,
 		// Include Difficulty pallet into runtime
-		Difficulty: pallet_difficulty::{Pallet, Call, Storage, Config} = 19,
+		Difficulty: pallet_difficulty::{Pallet, Call, Storage, Config, Event<T>} = 19,
 		// Include Contracts pallet into runtime
 		Contracts: pallet_contracts,
+		// Include Rewards pallet into runtime
+		Rewards: pallet_rewards,
+		Authorship: pallet_authorship,
 
 	}
 );
@@ -380,6 +439,12 @@ pub type SignedExtra = (
 pub type UncheckedExtrinsic = generic::UncheckedExtrinsic<Address, Call, Signature, SignedExtra>;
 /// The payload being signed in transactions.
 pub type SignedPayload = generic::SignedPayload<Call, SignedExtra>;
+/// Storage migrations run on the next runtime upgrade.
+pub type Migrations = (
+	pallet_difficulty::migrations::v1::MigrateToV1<Runtime>,
+	pallet_difficulty::migrations::v2::MigrateToV2<Runtime>,
+	pallet_difficulty::migrations::v3::MigrateToV3<Runtime>,
+);
 /// Executive: handles dispatch to the various modules.
 pub type Executive = frame_executive::Executive<
 	Runtime,
@@ -387,6 +452,7 @@ pub type Executive = frame_executive::Executive<
 	frame_system::ChainContext<Runtime>,
 	Runtime,
 	AllPalletsWithSystem,
+	Migrations,
 >;
 
 #[cfg(feature = "runtime-benchmarks")]
@@ -400,6 +466,8 @@ mod benches {
 		[frame_system, SystemBench::<Runtime>]
 		[pallet_balances, Balances]
 		[pallet_timestamp, Timestamp]
+		[pallet_difficulty, Difficulty]
+		[pallet_rewards, Rewards]
 	);
 }
 
@@ -476,11 +544,53 @@ impl_runtime_apis! {
 
 	impl sp_consensus_pow::DifficultyApi<Block, crain_primitives::Difficulty> for Runtime {
 		fn difficulty() -> crain_primitives::Difficulty {
-			pallet_difficulty::Module::<Runtime>::difficulty()
+			Difficulty::difficulty()
 		}
 	}
 
 
+	impl crain_primitives::RewardsApi<Block, AccountId, BlockNumber, Balance> for Runtime {
+		fn current_subsidy() -> Balance {
+			Rewards::current_subsidy()
+		}
+
+		fn next_subsidy() -> Balance {
+			Rewards::next_subsidy()
+		}
+
+		fn immature_rewards(
+			account: AccountId,
+		) -> Vec<crain_primitives::ImmatureReward<BlockNumber, Balance>> {
+			Rewards::immature_rewards(account).into_inner()
+		}
+	}
+
+	impl crain_primitives::DifficultyHistoryApi<Block, u64> for Runtime {
+		fn difficulty_window() -> Vec<crain_primitives::DifficultyAndTimestamp<u64>> {
+			Difficulty::window()
+		}
+
+		fn predicted_difficulty() -> crain_primitives::Difficulty {
+			Difficulty::predicted_difficulty()
+		}
+
+		fn hashrate() -> crain_primitives::Difficulty {
+			Difficulty::hashrate()
+		}
+	}
+
+	impl crain_primitives::PowAlgorithmApi<Block> for Runtime {
+		fn pow_algorithm() -> crain_primitives::hash::HashAlgorithm {
+			Difficulty::pow_algorithm()
+		}
+	}
+
+	impl crain_primitives::AuthorshipApi<Block, AccountId> for Runtime {
+		fn author() -> Option<AccountId> {
+			Authorship::author()
+		}
+	}
+
 	impl frame_system_rpc_runtime_api::AccountNonceApi<Block, AccountId, Index> for Runtime {
 		fn account_nonce(account: AccountId) -> Index {
 			System::account_nonce(account)